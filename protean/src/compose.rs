@@ -0,0 +1,156 @@
+//! Squashing a sequence of patches into one
+//!
+//! Change feeds tend to produce many small patches against the same object. Composing them gives a
+//! single patch with the same end result, which is cheaper to send and apply.
+
+use crate::{impls::json, local::*};
+
+impl<'a> Patch<'a> {
+  /// Create a single patch with the same effect as applying first and then second
+  ///
+  /// Later whole value changes replace earlier ones, nested patches are composed recursively, and
+  /// actions that undo each other (such as inserting then removing the same list index) are dropped.
+  /// Any other pair of actions on a field becomes a sequence of steps.
+  pub fn compose(first: Patch<'a>, second: Patch<'a>) -> Result<Patch<'a>, ProteanError> {
+    compose_patch(first, second, "")
+  }
+}
+
+fn compose_patch<'a>(
+  mut first: Patch<'a>,
+  second: Patch<'a>,
+  prefix: &str,
+) -> Result<Patch<'a>, ProteanError> {
+  for next in second.into_actions() {
+    let name = next.get_name();
+    let path = match prefix.is_empty() {
+      true => name.clone(),
+      false => format!("{}.{}", prefix, name),
+    };
+
+    let composed = match first.remove(&name) {
+      Some(prev) => compose_action(prev, next, &path)?,
+      None => Some(next),
+    };
+    if let Some(action) = composed {
+      first.add_action(action)?;
+    }
+  }
+  Ok(first)
+}
+
+/// Combine two consecutive actions on the same field, returning None if they cancel out
+fn compose_action<'a>(
+  prev: PatchAction<'a>,
+  next: PatchAction<'a>,
+  path: &str,
+) -> Result<Option<PatchAction<'a>>, ProteanError> {
+  let name = prev.get_name();
+  // The composed patch is applied to the state before prev, so that is the one to check against
+  let expected = prev.get_expected();
+  let is_value = |act: &PatchAction| matches!(act.get_value(), PatchValue::Value(_));
+
+  let action = match (prev.get_action().clone(), next.get_action().clone()) {
+    (_, Action::Null) => prev,
+    (Action::Null, _) => next,
+
+    // Replacing the whole value makes anything done before it irrelevant
+    (_, Action::Reset) | (_, Action::Clear) => with_expected(next, expected),
+    (_, Action::Set) if is_value(&next) => with_expected(next, expected),
+
+    // A value that was set can have the change made to it directly
+    (Action::Set, _) if is_value(&prev) => {
+      let mut value = prev.get_value().as_json()?;
+      json::apply_action(&mut value, next)?;
+      PatchAction::new(Action::Set, OwnedValue::new(name, value), expected)
+    }
+    (Action::Set, Action::Set) => {
+      let patch = compose_patch(
+        prev.into_value().into_patch()?,
+        next.into_value().into_patch()?,
+        path,
+      )?;
+      if patch.is_empty() {
+        return Ok(None);
+      }
+      PatchAction::from_value(Action::Set, PatchValue::Patch(patch), expected)
+    }
+
+    (Action::List(ListAction::Insert(added)), Action::List(ListAction::Remove(removed)))
+      if added == removed =>
+    {
+      return Ok(None)
+    }
+
+    // Removing a member only succeeds if it was there, so adding it back leaves the set unchanged
+    (Action::Members(SetAction::Remove), Action::Members(SetAction::Add))
+      if prev.get_value().as_json()? == next.get_value().as_json()? =>
+    {
+      return Ok(None)
+    }
+    (Action::Map(MapAction::Insert(key)), Action::Map(MapAction::Update(updated)))
+      if key == updated =>
+    {
+      let mut value = prev.get_value().as_json()?;
+      json::update(&mut value, next.into_value())?;
+      PatchAction::new(
+        Action::Map(MapAction::Insert(key)),
+        OwnedValue::new(name, value),
+        expected,
+      )
+    }
+    (Action::Map(MapAction::Update(key)), Action::Map(MapAction::Update(updated)))
+      if key == updated =>
+    {
+      let value = match (prev.into_value(), next.into_value()) {
+        (_, PatchValue::Value(val)) => PatchValue::Value(val),
+        (PatchValue::Value(val), PatchValue::Patch(patch)) => {
+          let mut value = val.as_json()?;
+          json::update(&mut value, PatchValue::Patch(patch))?;
+          PatchValue::Value(Box::new(OwnedValue::new(name, value)))
+        }
+        (PatchValue::Patch(left), PatchValue::Patch(right)) => {
+          let patch = compose_patch(left, right, &format!("{}.{}", path, key))?;
+          if patch.is_empty() {
+            return Ok(None);
+          }
+          PatchValue::Patch(patch)
+        }
+        _ => return Err(ProteanError::InvalidPatchType),
+      };
+      PatchAction::from_value(Action::Map(MapAction::Update(key)), value, expected)
+    }
+    (Action::Map(MapAction::Update(key)), Action::Map(MapAction::Delete(deleted)))
+      if key == deleted =>
+    {
+      with_expected(next, expected)
+    }
+    (Action::Map(MapAction::Delete(key)), Action::Map(MapAction::Insert(added)))
+      if key == added =>
+    {
+      PatchAction::from_value(
+        Action::Map(MapAction::Update(key)),
+        next.into_value(),
+        expected,
+      )
+    }
+
+    // Anything else is kept as a sequence, so the steps still run one after the other. This includes
+    // inserting a key or adding a member and then removing it, as it may already have been there.
+    _ => PatchAction::from_steps(
+      name,
+      prev
+        .into_steps()
+        .into_iter()
+        .chain(next.into_steps())
+        .collect(),
+    ),
+  };
+  Ok(Some(action))
+}
+
+/// Swap out the precondition on an action
+fn with_expected(action: PatchAction, expected: Option<u64>) -> PatchAction {
+  let act = action.get_action().clone();
+  PatchAction::from_value(act, action.into_value(), expected)
+}
@@ -3,6 +3,8 @@
 use std::fmt::Display;
 use thiserror::Error;
 
+use crate::merge::Conflict;
+
 #[derive(Debug, Clone, Error)]
 #[cfg_attr(
   feature = "serde_support",
@@ -21,10 +23,56 @@ pub enum ProteanError {
   #[error("Could not find the field with the given name")]
   FieldNotFound,
 
+  #[error("Could not find the key '{0}'")]
+  KeyNotFound(String),
+
+  #[error("The index {0} is out of range for the list")]
+  IndexOutOfRange(usize),
+
+  #[error("The action '{0}' is not supported by this value")]
+  UnsupportedAction(String),
+
+  #[error("The field '{0}' cannot be changed")]
+  ImmutableField(String),
+
+  #[error(
+    "The patches have conflicting changes to: {}",
+    .0.iter().map(|c| c.path.as_str()).collect::<Vec<_>>().join(", ")
+  )]
+  MergeConflict(Vec<Conflict>),
+
+  #[error("The change to '{0}' cannot be moved past a concurrent change to the same elements")]
+  RebaseConflict(String),
+
+  #[error("Expected '{path}' to hash to {expected}, but it hashed to {found}")]
+  ExpectedMismatch {
+    path: String,
+    expected: u64,
+    found: u64,
+  },
+
   #[error("Error (de)serializing the patch: {0}")]
   SerializationError(String),
 }
 
+impl ProteanError {
+  /// Add the name of the field an error came from to the start of any path it reports
+  pub fn within(self, field: &str) -> ProteanError {
+    match self {
+      ProteanError::ExpectedMismatch {
+        path,
+        expected,
+        found,
+      } => ProteanError::ExpectedMismatch {
+        path: format!("{}.{}", field, path),
+        expected,
+        found,
+      },
+      err => err,
+    }
+  }
+}
+
 impl serde::ser::Error for ProteanError {
   fn custom<T: Display>(msg: T) -> Self {
     ProteanError::SerializationError(msg.to_string())
@@ -0,0 +1,119 @@
+//! Struct fields that don't need an enum of their own
+//!
+//! Patchwork finds fields through its Accessor and Element types. A struct whose fields are all
+//! Patchable can use FieldName and Field for these, so diffing and applying is handled by each field's
+//! own type instead of a hand written enum matching on every field.
+
+use crate::local::*;
+
+use std::any::Any;
+
+/// The name of a field, as the Accessor of a struct that uses Field for its values
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FieldName(String);
+
+impl FieldName {
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+impl FromStr for FieldName {
+  type Err = ProteanError;
+
+  fn from_str(name: &str) -> Result<FieldName, ProteanError> {
+    Ok(FieldName(name.to_string()))
+  }
+}
+
+impl Display for FieldName {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+/// A named reference to the value of any Patchable field
+///
+/// Two fields are diffed by their own type's `diff_value`, so a list field gives list actions and a
+/// nested struct a nested patch. Diffing fields holding different types is an InvalidPatchType error.
+#[derive(Debug, Clone)]
+pub struct Field<'a> {
+  name: String,
+  value: &'a dyn AnyPatchable<'a>,
+}
+
+impl<'a> Field<'a> {
+  pub fn new<T: Patchable<'a> + Send + Sync + 'static>(name: &str, value: &'a T) -> Field<'a> {
+    Field {
+      name: name.to_string(),
+      value,
+    }
+  }
+}
+
+impl<'a> Patchworthy<'a> for Field<'a> {
+  fn get_field_name(&self) -> String {
+    self.name.clone()
+  }
+
+  fn as_json(&self) -> Result<serde_json::Value, ProteanError> {
+    self.value.to_json()
+  }
+
+  fn diff(&self, other: Self) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    self.value.diff_any(&self.name, other.value)
+  }
+}
+
+/// Serializes as a single entry map, the same shape as a derived field enum
+impl<'a> Serialize for Field<'a> {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::{Error, SerializeMap};
+
+    let value = self.value.to_json().map_err(S::Error::custom)?;
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(&self.name, &value)?;
+    map.end()
+  }
+}
+
+impl<'a> Display for Field<'a> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}: {:?}", self.name, self.value)
+  }
+}
+
+/// A Patchable value with its type erased, so fields of different types can share a Vec
+pub trait AnyPatchable<'a>: Debug + Send + Sync {
+  fn as_any(&self) -> &dyn Any;
+
+  fn to_json(&self) -> Result<serde_json::Value, ProteanError>;
+
+  /// Diff against another value, which must have the same type as this one
+  fn diff_any(
+    &'a self,
+    name: &str,
+    other: &'a dyn AnyPatchable<'a>,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError>;
+}
+
+impl<'a, T: Patchable<'a> + Send + Sync + 'static> AnyPatchable<'a> for T {
+  fn as_any(&self) -> &dyn Any {
+    self
+  }
+
+  fn to_json(&self) -> Result<serde_json::Value, ProteanError> {
+    Ok(serde_json::to_value(self)?)
+  }
+
+  fn diff_any(
+    &'a self,
+    name: &str,
+    other: &'a dyn AnyPatchable<'a>,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    match other.as_any().downcast_ref::<T>() {
+      Some(other) => self.diff_value(name, other),
+      None => Err(ProteanError::InvalidPatchType),
+    }
+  }
+}
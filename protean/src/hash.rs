@@ -0,0 +1,66 @@
+//! Stable hashing of values, used for the expected value of a PatchAction
+//!
+//! The hash has to come out the same on every machine and Rust release, so it can't use
+//! `std::hash`. Instead a value is written out as canonical JSON and hashed with 64 bit FNV-1a.
+//!
+//! Canonical JSON is the compact form produced by serde_json (no whitespace, strings escaped the same
+//! way), with the keys of every object written in sorted order. Numbers use serde_json's formatting,
+//! which is the shortest representation that reads back to the same value. Anything that serializes
+//! to the same JSON will have the same hash, regardless of the Rust type it came from.
+
+use crate::local::*;
+
+use serde_json::Value;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Hash a JSON value with the canonical scheme described above
+pub fn content_hash(value: &Value) -> u64 {
+  let mut canonical = String::new();
+  write_canonical(value, &mut canonical);
+  fnv1a(canonical.as_bytes())
+}
+
+/// Hash anything that can be serialized, such as a full Patchwork object
+pub fn hash_serialize<T: Serialize + ?Sized>(value: &T) -> Result<u64, ProteanError> {
+  Ok(content_hash(&serde_json::to_value(value)?))
+}
+
+/// 64 bit FNV-1a over the given bytes
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(FNV_OFFSET, |hash, byte| {
+    (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+  })
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+  match value {
+    Value::Object(map) => {
+      let mut fields: Vec<(&String, &Value)> = map.iter().collect();
+      fields.sort_by(|left, right| left.0.cmp(right.0));
+
+      out.push('{');
+      for (idx, (key, field)) in fields.into_iter().enumerate() {
+        if idx > 0 {
+          out.push(',');
+        }
+        out.push_str(&Value::String(key.clone()).to_string());
+        out.push(':');
+        write_canonical(field, out);
+      }
+      out.push('}');
+    }
+    Value::Array(list) => {
+      out.push('[');
+      for (idx, item) in list.iter().enumerate() {
+        if idx > 0 {
+          out.push(',');
+        }
+        write_canonical(item, out);
+      }
+      out.push(']');
+    }
+    other => out.push_str(&other.to_string()),
+  }
+}
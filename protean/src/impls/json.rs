@@ -1,3 +1,384 @@
 //! An implementation of a patch for a serde_json::Value
 //!
-//! Creating and minipulate JSON objects that are not concretely defined.
+//! Creating and minipulate JSON objects that are not concretely defined. The patches use the same
+//! format as typed structs, so JSON can be diffed or patched without knowing the Rust type it is for.
+
+use super::*;
+
+use serde_json::{Map, Value};
+
+/// Compare two JSON values, returning the action that turns old into new or None if they are the same
+///
+/// Objects give a Map.Delete or Map.Insert for each key only one side has, and a nested patch for the
+/// keys whose values changed, so objects with the same keys diff the same way a struct does. Arrays
+/// are diffed into list actions by `list::diff_list`. Anything else, including a change of type, is a
+/// Set of the new value.
+pub fn diff<'a>(
+  name: &str,
+  old: &Value,
+  new: &Value,
+) -> Result<Option<PatchAction<'a>>, ProteanError> {
+  match (old, new) {
+    _ if old == new => Ok(None),
+    (Value::Object(old), Value::Object(new)) => diff_object(name, old, new),
+    (Value::Array(old), Value::Array(new)) => list::diff_list(name, old, new),
+    _ => Ok(Some(PatchAction::new(
+      Action::Set,
+      OwnedValue::new(name.to_string(), new.clone()),
+      None,
+    ))),
+  }
+}
+
+fn diff_object<'a>(
+  name: &str,
+  old: &Map<String, Value>,
+  new: &Map<String, Value>,
+) -> Result<Option<PatchAction<'a>>, ProteanError> {
+  let entry = |act: MapAction, value: Value| {
+    PatchAction::new(
+      Action::Map(act),
+      OwnedValue::new(name.to_string(), value),
+      None,
+    )
+  };
+
+  let mut steps = Vec::new();
+  let mut changed = Patch::new(name.to_string());
+  for (key, value) in old {
+    match new.get(key) {
+      None => steps.push(entry(MapAction::Delete(key.clone()), Value::Null)),
+      Some(other) => {
+        if let Some(action) = diff(key, value, other)? {
+          changed.add_action(action)?;
+        }
+      }
+    }
+  }
+  if !changed.is_empty() {
+    steps.push(PatchAction::from_patch(name.to_string(), changed));
+  }
+  for (key, value) in new {
+    if !old.contains_key(key) {
+      steps.push(entry(MapAction::Insert(key.clone()), value.clone()));
+    }
+  }
+
+  Ok(match steps.is_empty() {
+    true => None,
+    false => Some(PatchAction::from_steps(name.to_string(), steps)),
+  })
+}
+
+/// A JSON value can be a field of its own, for data that has no fixed shape
+impl<'a> Patchable<'a> for Value {
+  fn diff_value(
+    &'a self,
+    name: &str,
+    other: &'a Self,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    diff(name, self, other)
+  }
+
+  fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+    apply_action(self, action)
+  }
+}
+
+/// Perform an action against a JSON value in place, returning the action that will undo it
+///
+/// Reset is not supported, as JSON has no concept of a default value. Sets are stored as arrays of
+/// unique values. The steps of a sequence are applied in order, and the value is left untouched if
+/// any of them fail.
+pub fn apply_action<'a>(
+  target: &mut Value,
+  action: PatchAction,
+) -> Result<PatchAction<'a>, ProteanError> {
+  if let Action::Steps = action.get_action() {
+    let name = action.get_name();
+    let original = target.clone();
+    let mut undo = Vec::new();
+    for step in action.into_steps() {
+      match apply_action(target, step) {
+        Ok(step) => undo.push(step),
+        Err(err) => {
+          *target = original;
+          return Err(err);
+        }
+      }
+    }
+    undo.reverse();
+    return Ok(PatchAction::from_steps(name, undo));
+  }
+
+  // Also checks that any indices and keys the action needs exist
+  let undo = invert_action(target, &action)?;
+
+  match action.get_action().clone() {
+    Action::Null | Action::Steps => (),
+    Action::Reset => return Err(ProteanError::UnsupportedAction("Reset".to_string())),
+    Action::Clear => clear(target)?,
+    Action::Set => update(target, action.into_value())?,
+    Action::List(act) => {
+      let list = target
+        .as_array_mut()
+        .ok_or(ProteanError::InvalidPatchType)?;
+      match act {
+        ListAction::Swap(left, right) => list.swap(left, right),
+        ListAction::Remove(idx) => {
+          list.remove(idx);
+        }
+        ListAction::Insert(idx) => list.insert(idx, action.get_value().as_json()?),
+        ListAction::Append() => list.push(action.get_value().as_json()?),
+        ListAction::Move(from, to) => {
+          let value = list.remove(from);
+          list.insert(to, value);
+        }
+        ListAction::Update(idx) => {
+          let entry = list
+            .get_mut(idx)
+            .ok_or(ProteanError::IndexOutOfRange(idx))?;
+          update(entry, action.into_value())?;
+        }
+      }
+    }
+    Action::Members(act) => {
+      let set = target
+        .as_array_mut()
+        .ok_or(ProteanError::InvalidPatchType)?;
+      let value = action.get_value().as_json()?;
+      match (act, set.iter().position(|member| *member == value)) {
+        (SetAction::Add, None) => set.push(value),
+        (SetAction::Remove, Some(idx)) => {
+          set.remove(idx);
+        }
+        // Already checked when creating the undo
+        _ => (),
+      }
+    }
+    Action::Map(act) => {
+      let map = target
+        .as_object_mut()
+        .ok_or(ProteanError::InvalidPatchType)?;
+      match act {
+        MapAction::Insert(key) => {
+          if map.contains_key(&key) && !action.get_options().allow_upsert() {
+            return Err(ProteanError::DuplicateKey);
+          }
+          map.insert(key, action.get_value().as_json()?);
+        }
+        MapAction::Update(key) => {
+          let entry = map.get_mut(&key).ok_or(ProteanError::KeyNotFound(key))?;
+          update(entry, action.into_value())?;
+        }
+        MapAction::Delete(key) => {
+          map.remove(&key);
+        }
+      }
+    }
+  }
+  Ok(undo)
+}
+
+/// Apply each action in a patch to the matching key of a JSON object, or index of an array
+///
+/// The object is left untouched if any of the actions fail, including an expected hash that doesn't
+/// match when the patch options say to fail.
+pub fn apply_patch<'a>(target: &mut Value, patch: Patch) -> Result<Patch<'a>, ProteanError> {
+  let options = patch.get_options().cloned().unwrap_or_default();
+  let original = target.clone();
+  let mut reverse = Patch::new(patch.get_name());
+  for mut action in patch.into_actions() {
+    let name = action.get_name();
+    action.inherit_options(&options);
+    let result = child_mut(target, &name).and_then(|field| {
+      match action.check_expected(field, options.on_mismatch())? {
+        true => apply_action(field, action)
+          .map_err(|err| err.within(&name))
+          .and_then(|undo| reverse.add_action(undo)),
+        false => Ok(()),
+      }
+    });
+
+    if let Err(err) = result {
+      *target = original;
+      return Err(err);
+    }
+  }
+  Ok(reverse)
+}
+
+/// Create the action that will undo the given one, based on the current value
+///
+/// Nothing is changed, this only looks at the values the action is about to overwrite or remove.
+pub fn invert_action<'a>(
+  target: &Value,
+  action: &PatchAction,
+) -> Result<PatchAction<'a>, ProteanError> {
+  let name = action.get_name();
+  let undo = |act: Action, value: Value| PatchAction::new(act, OwnedValue::new(name, value), None);
+
+  Ok(match action.get_action().clone() {
+    Action::Null => undo(Action::Null, Value::Null),
+    Action::Reset | Action::Clear => undo(Action::Set, target.clone()),
+    Action::Set => match action.get_value() {
+      PatchValue::Value(_) => undo(Action::Set, target.clone()),
+      PatchValue::Patch(patch) => {
+        PatchAction::from_patch(patch.get_name(), invert_patch(target, patch)?)
+      }
+      PatchValue::Steps(_, _) => return Err(ProteanError::InvalidPatchType),
+    },
+    // Each step depends on the ones before it, so they are run against a copy
+    Action::Steps => apply_action(&mut target.clone(), action.try_clone()?)?,
+    Action::List(act) => {
+      let list = target.as_array().ok_or(ProteanError::InvalidPatchType)?;
+      let get = |idx: usize| list.get(idx).ok_or(ProteanError::IndexOutOfRange(idx));
+
+      match act {
+        ListAction::Swap(left, right) => {
+          get(left)?;
+          get(right)?;
+          undo(Action::List(ListAction::Swap(left, right)), Value::Null)
+        }
+        ListAction::Remove(idx) => undo(Action::List(ListAction::Insert(idx)), get(idx)?.clone()),
+        ListAction::Insert(idx) if idx > list.len() => {
+          return Err(ProteanError::IndexOutOfRange(idx))
+        }
+        ListAction::Insert(idx) => undo(Action::List(ListAction::Remove(idx)), Value::Null),
+        ListAction::Append() => undo(Action::List(ListAction::Remove(list.len())), Value::Null),
+        ListAction::Move(from, to) => {
+          get(from)?;
+          get(to)?;
+          undo(Action::List(ListAction::Move(to, from)), Value::Null)
+        }
+        ListAction::Update(idx) => match action.get_value() {
+          PatchValue::Value(_) => undo(Action::List(ListAction::Update(idx)), get(idx)?.clone()),
+          PatchValue::Patch(patch) => PatchAction::from_value(
+            Action::List(ListAction::Update(idx)),
+            PatchValue::Patch(invert_patch(get(idx)?, patch)?),
+            None,
+          ),
+          PatchValue::Steps(_, _) => return Err(ProteanError::InvalidPatchType),
+        },
+      }
+    }
+    Action::Members(act) => {
+      let set = target.as_array().ok_or(ProteanError::InvalidPatchType)?;
+      let value = action.get_value().as_json()?;
+      let found = set.contains(&value);
+      match act {
+        SetAction::Add if found && !action.get_options().allow_upsert() => {
+          return Err(ProteanError::DuplicateKey)
+        }
+        SetAction::Add if found => undo(Action::Null, Value::Null),
+        SetAction::Add => undo(Action::Members(SetAction::Remove), value),
+        SetAction::Remove if found => undo(Action::Members(SetAction::Add), value),
+        SetAction::Remove => return Err(ProteanError::KeyNotFound(value.to_string())),
+      }
+    }
+    Action::Map(act) => {
+      let map = target.as_object().ok_or(ProteanError::InvalidPatchType)?;
+      let get = |key: &String| {
+        map
+          .get(key)
+          .ok_or_else(|| ProteanError::KeyNotFound(key.clone()))
+      };
+
+      match act {
+        MapAction::Insert(key) => match map.get(&key) {
+          Some(old) => undo(Action::Map(MapAction::Update(key)), old.clone()),
+          None => undo(Action::Map(MapAction::Delete(key)), Value::Null),
+        },
+        MapAction::Update(key) => match action.get_value() {
+          PatchValue::Value(_) => undo(
+            Action::Map(MapAction::Update(key.clone())),
+            get(&key)?.clone(),
+          ),
+          PatchValue::Patch(patch) => PatchAction::from_value(
+            Action::Map(MapAction::Update(key.clone())),
+            PatchValue::Patch(invert_patch(get(&key)?, patch)?),
+            None,
+          ),
+          PatchValue::Steps(_, _) => return Err(ProteanError::InvalidPatchType),
+        },
+        MapAction::Delete(key) => undo(
+          Action::Map(MapAction::Insert(key.clone())),
+          get(&key)?.clone(),
+        ),
+      }
+    }
+  })
+}
+
+/// Create the patch that will undo the given one when applied to a JSON object
+pub fn invert_patch<'a>(target: &Value, patch: &Patch) -> Result<Patch<'a>, ProteanError> {
+  let mut reverse = Patch::new(patch.get_name());
+  for action in patch.get_actions() {
+    let field = child(target, &action.get_name())?;
+    reverse.add_action(invert_action(field, action)?)?;
+  }
+  Ok(reverse)
+}
+
+/// Find the field of an object, or the element of an array when the name is an index
+///
+/// Arrays are patched by index when they hold a tuple or fixed size array, as there is nothing to
+/// insert or remove.
+pub fn child<'v>(target: &'v Value, name: &str) -> Result<&'v Value, ProteanError> {
+  match target {
+    Value::Object(obj) => obj.get(name).ok_or(ProteanError::FieldNotFound),
+    Value::Array(list) => {
+      let idx = index(name)?;
+      list.get(idx).ok_or(ProteanError::IndexOutOfRange(idx))
+    }
+    _ => Err(ProteanError::InvalidPatchType),
+  }
+}
+
+/// The mutable version of `child`
+pub fn child_mut<'v>(target: &'v mut Value, name: &str) -> Result<&'v mut Value, ProteanError> {
+  match target {
+    Value::Object(obj) => obj.get_mut(name).ok_or(ProteanError::FieldNotFound),
+    Value::Array(list) => {
+      let idx = index(name)?;
+      list.get_mut(idx).ok_or(ProteanError::IndexOutOfRange(idx))
+    }
+    _ => Err(ProteanError::InvalidPatchType),
+  }
+}
+
+fn index(name: &str) -> Result<usize, ProteanError> {
+  name.parse().map_err(|_| ProteanError::FieldNotFound)
+}
+
+/// Replace a value, or apply a nested patch to it
+pub fn update(target: &mut Value, value: PatchValue) -> Result<(), ProteanError> {
+  match value {
+    PatchValue::Value(val) => *target = val.as_json()?,
+    PatchValue::Patch(patch) => {
+      apply_patch(target, patch)?;
+    }
+    PatchValue::Steps(_, _) => return Err(ProteanError::InvalidPatchType),
+  }
+  Ok(())
+}
+
+/// Set a value to its version of null
+///
+/// Numbers become 0, booleans false, strings and lists are emptied, and objects have each of their
+/// fields cleared. Null is already empty, so it is left alone.
+pub fn clear(target: &mut Value) -> Result<(), ProteanError> {
+  match target {
+    Value::Number(_) => *target = Value::from(0),
+    Value::Bool(val) => *val = false,
+    Value::String(val) => val.clear(),
+    Value::Array(val) => val.clear(),
+    Value::Object(fields) => {
+      for field in fields.values_mut() {
+        clear(field)?;
+      }
+    }
+    Value::Null => (),
+  }
+  Ok(())
+}
@@ -1,6 +1,566 @@
 //! Patchwork implementions for ordered sets of values
 
-pub enum ListAction {
-  Insert(u16),
-  Remove(u16),
+use super::*;
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+pub use crate::patch::ListAction;
+
+/// The operations needed to apply a ListAction directly to a list, without converting it to JSON
+pub trait ListLike<T> {
+  fn len(&self) -> usize;
+
+  fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  fn get(&self, idx: usize) -> Option<&T>;
+
+  fn get_mut(&mut self, idx: usize) -> Option<&mut T>;
+
+  fn swap(&mut self, left: usize, right: usize);
+
+  fn insert(&mut self, idx: usize, value: T);
+
+  fn remove(&mut self, idx: usize) -> Option<T>;
+
+  fn push(&mut self, value: T);
+}
+
+impl<T> ListLike<T> for Vec<T> {
+  fn len(&self) -> usize {
+    Vec::len(self)
+  }
+
+  fn get(&self, idx: usize) -> Option<&T> {
+    <[T]>::get(self, idx)
+  }
+
+  fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+    <[T]>::get_mut(self, idx)
+  }
+
+  fn swap(&mut self, left: usize, right: usize) {
+    <[T]>::swap(self, left, right)
+  }
+
+  fn insert(&mut self, idx: usize, value: T) {
+    Vec::insert(self, idx, value)
+  }
+
+  fn remove(&mut self, idx: usize) -> Option<T> {
+    match idx < Vec::len(self) {
+      true => Some(Vec::remove(self, idx)),
+      false => None,
+    }
+  }
+
+  fn push(&mut self, value: T) {
+    Vec::push(self, value)
+  }
+}
+
+impl<T> ListLike<T> for VecDeque<T> {
+  fn len(&self) -> usize {
+    VecDeque::len(self)
+  }
+
+  fn get(&self, idx: usize) -> Option<&T> {
+    VecDeque::get(self, idx)
+  }
+
+  fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+    VecDeque::get_mut(self, idx)
+  }
+
+  fn swap(&mut self, left: usize, right: usize) {
+    VecDeque::swap(self, left, right)
+  }
+
+  fn insert(&mut self, idx: usize, value: T) {
+    VecDeque::insert(self, idx, value)
+  }
+
+  fn remove(&mut self, idx: usize) -> Option<T> {
+    VecDeque::remove(self, idx)
+  }
+
+  fn push(&mut self, value: T) {
+    VecDeque::push_back(self, value)
+  }
+}
+
+/// Apply an action to a Vec or VecDeque, returning the action that will undo it
+///
+/// List actions work on the elements in place, so only the inserted or updated value is deserialized
+/// and only the removed or updated one serialized. Anything else is treated as a whole value by `apply_value`.
+pub fn apply_list<'a, L, T>(
+  list: &mut L,
+  action: PatchAction,
+) -> Result<PatchAction<'a>, ProteanError>
+where
+  L: ListLike<T> + Serialize + DeserializeOwned + Default,
+  T: Serialize + DeserializeOwned,
+{
+  let act = match action.get_action() {
+    Action::List(act) => act.clone(),
+    _ => return primitives::apply_value(list, action),
+  };
+  let name = action.get_name();
+  let undo = |act, value| PatchAction::new(Action::List(act), OwnedValue::new(name, value), None);
+  let check = |list: &L, idx: usize| match idx < list.len() {
+    true => Ok(()),
+    false => Err(ProteanError::IndexOutOfRange(idx)),
+  };
+  let value =
+    || -> Result<T, ProteanError> { Ok(serde_json::from_value(action.get_value().as_json()?)?) };
+
+  Ok(match act {
+    ListAction::Swap(left, right) => {
+      check(list, left)?;
+      check(list, right)?;
+      list.swap(left, right);
+      undo(ListAction::Swap(left, right), serde_json::Value::Null)
+    }
+    ListAction::Remove(idx) => {
+      let old = serde_json::to_value(list.get(idx).ok_or(ProteanError::IndexOutOfRange(idx))?)?;
+      list.remove(idx);
+      undo(ListAction::Insert(idx), old)
+    }
+    ListAction::Insert(idx) => {
+      if idx > list.len() {
+        return Err(ProteanError::IndexOutOfRange(idx));
+      }
+      list.insert(idx, value()?);
+      undo(ListAction::Remove(idx), serde_json::Value::Null)
+    }
+    ListAction::Append() => {
+      let idx = list.len();
+      list.push(value()?);
+      undo(ListAction::Remove(idx), serde_json::Value::Null)
+    }
+    ListAction::Move(from, to) => {
+      check(list, from)?;
+      check(list, to)?;
+      if let Some(value) = list.remove(from) {
+        list.insert(to, value);
+      }
+      undo(ListAction::Move(to, from), serde_json::Value::Null)
+    }
+    ListAction::Update(idx) => {
+      let entry = list
+        .get_mut(idx)
+        .ok_or(ProteanError::IndexOutOfRange(idx))?;
+      let mut json = serde_json::to_value(&*entry)?;
+      let field = action.get_name();
+      let reverse = match action.into_value() {
+        PatchValue::Value(value) => PatchValue::Value(Box::new(OwnedValue::new(
+          field,
+          std::mem::replace(&mut json, value.as_json()?),
+        ))),
+        PatchValue::Patch(patch) => PatchValue::Patch(json::apply_patch(&mut json, patch)?),
+        PatchValue::Steps(_, _) => return Err(ProteanError::InvalidPatchType),
+      };
+      *entry = serde_json::from_value(json)?;
+      PatchAction::from_value(Action::List(ListAction::Update(idx)), reverse, None)
+    }
+  })
+}
+
+/// Compare two lists, returning the steps that turn old into new or None if they are the same
+///
+/// Myers' algorithm finds the fewest removes and inserts, so a long list with one changed element
+/// only sends that element. An element that was removed from one place and inserted at another is
+/// moved instead of being sent again. When it would take more edits than half the length of the new
+/// list, the whole value is Set instead, as it is about as big and much cheaper to find.
+pub fn diff_list<'a, L, T>(
+  name: &str,
+  old: &L,
+  new: &L,
+) -> Result<Option<PatchAction<'a>>, ProteanError>
+where
+  L: ListLike<T> + Serialize,
+  T: PartialEq + Serialize,
+{
+  let edits = match shortest_edit(old, new, new.len() / 2) {
+    Some(edits) => edits,
+    None => {
+      let value = OwnedValue::new(name.to_string(), serde_json::to_value(new)?);
+      return Ok(Some(PatchAction::new(Action::Set, value, None)));
+    }
+  };
+
+  // The element of the old list that each element of the new one is, if any
+  let mut sources = Vec::with_capacity(new.len());
+  let mut removed = Vec::new();
+  let mut old_idx = 0;
+  for edit in edits {
+    match edit {
+      Edit::Keep => sources.push(Some(old_idx)),
+      Edit::Remove => removed.push(old_idx),
+      Edit::Insert => sources.push(None),
+    }
+    if !matches!(edit, Edit::Insert) {
+      old_idx += 1;
+    }
+  }
+  for (idx, source) in sources.iter_mut().enumerate() {
+    if source.is_none() {
+      if let Some(pos) = removed
+        .iter()
+        .position(|old_idx| old.get(*old_idx) == new.get(idx))
+      {
+        *source = Some(removed.remove(pos));
+      }
+    }
+  }
+
+  let mut steps = Vec::new();
+  for planned in plan_moves(old.len(), &sources) {
+    let (act, value) = match planned {
+      Planned::Act(act) => (act, serde_json::Value::Null),
+      Planned::Insert(act, idx) => (act, serde_json::to_value(new.get(idx))?),
+      Planned::Placed { .. } => continue,
+    };
+    steps.push(PatchAction::new(
+      Action::List(act),
+      OwnedValue::new(name.to_string(), value),
+      None,
+    ));
+  }
+  Ok(into_action(name, steps))
+}
+
+/// Compare two lists of records by their ids rather than their positions
+///
+/// Records are matched up with `Patchwork::get_id`, so one that changed is sent as a nested patch of
+/// the fields that changed, and one that was reordered only costs a move. New records are inserted
+/// and missing ones removed. Records without an id, or sharing one, can't be matched so the whole
+/// list is Set instead.
+pub fn diff_keyed<'a, L, T>(
+  name: &str,
+  old: &'a L,
+  new: &'a L,
+) -> Result<Option<PatchAction<'a>>, ProteanError>
+where
+  L: ListLike<T> + Serialize,
+  T: Patchwork<'a> + 'a,
+{
+  let (old_ids, new_ids) = match (record_ids(old), record_ids(new)) {
+    (Some(old_ids), Some(new_ids)) => (old_ids, new_ids),
+    _ => {
+      let value = OwnedValue::new(name.to_string(), serde_json::to_value(new)?);
+      return Ok(Some(PatchAction::new(Action::Set, value, None)));
+    }
+  };
+  let positions: HashMap<&String, usize> = old_ids
+    .iter()
+    .enumerate()
+    .map(|(idx, id)| (id, idx))
+    .collect();
+  let sources: Vec<Option<usize>> = new_ids
+    .iter()
+    .map(|id| positions.get(id).copied())
+    .collect();
+
+  let step = |act, value| PatchAction::from_value(Action::List(act), value, None);
+  let owned = |value| PatchValue::Value(Box::new(OwnedValue::new(name.to_string(), value)));
+  let record = |list: &'a L, idx: usize| list.get(idx).ok_or(ProteanError::IndexOutOfRange(idx));
+  let mut steps = Vec::new();
+  for planned in plan_moves(old.len(), &sources) {
+    match planned {
+      Planned::Act(act) => steps.push(step(act, owned(serde_json::Value::Null))),
+      Planned::Insert(act, idx) => {
+        steps.push(step(act, owned(serde_json::to_value(record(new, idx)?)?)))
+      }
+      Planned::Placed {
+        idx,
+        old: from,
+        new: to,
+      } => {
+        let patch = record(old, from)?.diff(record(new, to)?)?;
+        if !patch.is_empty() {
+          let value = PatchAction::from_patch(name.to_string(), patch).into_value();
+          steps.push(step(ListAction::Update(idx), value));
+        }
+      }
+    }
+  }
+  Ok(into_action(name, steps))
+}
+
+/// The steps as a single action, or None when there is nothing to do
+fn into_action<'a>(name: &str, steps: Vec<PatchAction<'a>>) -> Option<PatchAction<'a>> {
+  match steps.is_empty() {
+    true => None,
+    false => Some(PatchAction::from_steps(name.to_string(), steps)),
+  }
+}
+
+/// A step towards turning one list into another, as worked out by `plan_moves`
+enum Planned {
+  /// An action that needs no value
+  Act(ListAction),
+
+  /// Insert or append the element at this index of the new list
+  Insert(ListAction, usize),
+
+  /// An element from the old list is now at idx, where it is already in the right place
+  Placed { idx: usize, old: usize, new: usize },
+}
+
+/// Work out the removes, moves and inserts that turn the old list into the new one
+///
+/// Each source is the index of the old element that the new element at that position came from, or
+/// None for an element that is new. Old elements without a new position are removed first, then the
+/// new list is built from the front. The longest run of elements already in order are left alone, and
+/// everything else is moved straight to where it belongs.
+fn plan_moves(old_len: usize, sources: &[Option<usize>]) -> Vec<Planned> {
+  let mut planned = Vec::new();
+  let used: HashSet<usize> = sources.iter().flatten().copied().collect();
+  for idx in (0..old_len).rev() {
+    if !used.contains(&idx) {
+      planned.push(Planned::Act(ListAction::Remove(idx)));
+    }
+  }
+
+  let in_place = in_order(sources);
+  // The old index of each element as the steps are applied, or None for new elements
+  let mut current: Vec<Option<usize>> = (0..old_len)
+    .filter(|idx| used.contains(idx))
+    .map(Some)
+    .collect();
+  // Everything before the cursor is either in its final order, or waiting to be moved further on
+  let mut cursor = 0;
+  for (new, source) in sources.iter().enumerate() {
+    let old = match source {
+      Some(old) => *old,
+      None => {
+        let act = match cursor == current.len() {
+          true => ListAction::Append(),
+          false => ListAction::Insert(cursor),
+        };
+        planned.push(Planned::Insert(act, new));
+        current.insert(cursor, None);
+        cursor += 1;
+        continue;
+      }
+    };
+
+    let idx = match in_place.contains(&old) {
+      true => {
+        while current[cursor] != Some(old) {
+          cursor += 1;
+        }
+        cursor += 1;
+        cursor - 1
+      }
+      false => {
+        let from = match current.iter().position(|cur| *cur == Some(old)) {
+          Some(from) => from,
+          None => continue,
+        };
+        let to = cursor - usize::from(from < cursor);
+        if from != to {
+          planned.push(Planned::Act(ListAction::Move(from, to)));
+          let value = current.remove(from);
+          current.insert(to, value);
+        }
+        if from >= cursor {
+          cursor += 1;
+        }
+        to
+      }
+    };
+    planned.push(Planned::Placed { idx, old, new });
+  }
+  planned
+}
+
+/// The old indices in the longest run of sources that are already in increasing order
+fn in_order(sources: &[Option<usize>]) -> HashSet<usize> {
+  // The smallest old index that ends a run of each length, with its position in sources
+  let mut tails: Vec<(usize, usize)> = Vec::new();
+  let mut previous: Vec<Option<usize>> = vec![None; sources.len()];
+  for (pos, source) in sources.iter().enumerate() {
+    let old = match source {
+      Some(old) => *old,
+      None => continue,
+    };
+    let len = tails.partition_point(|(tail, _)| *tail < old);
+    if len > 0 {
+      previous[pos] = Some(tails[len - 1].1);
+    }
+    match len == tails.len() {
+      true => tails.push((old, pos)),
+      false => tails[len] = (old, pos),
+    }
+  }
+
+  let mut run = HashSet::new();
+  let mut next = tails.last().map(|(_, pos)| *pos);
+  while let Some(pos) = next {
+    run.extend(sources[pos]);
+    next = previous[pos];
+  }
+  run
+}
+
+/// A single step of an edit script, walking both lists from the front
+enum Edit {
+  Keep,
+  Remove,
+  Insert,
+}
+
+/// Find the shortest edit script from old to new, giving up once it needs more than limit edits
+///
+/// This is the greedy version of Myers' algorithm. The furthest point reached on each diagonal is
+/// kept for every round, so the path can be walked back once the end is reached.
+fn shortest_edit<L, T>(old: &L, new: &L, limit: usize) -> Option<Vec<Edit>>
+where
+  L: ListLike<T>,
+  T: PartialEq,
+{
+  let (n, m) = (old.len() as isize, new.len() as isize);
+  let max = (n + m).min(limit as isize);
+  let offset = max + 1;
+  // Furthest x reached on diagonal k (x - y), stored at k + offset
+  let mut furthest = vec![0; 2 * offset as usize + 1];
+  let mut trace: Vec<Vec<isize>> = Vec::new();
+
+  for d in 0..=max {
+    for k in (-d..=d).step_by(2) {
+      let down = k == -d
+        || (k != d && furthest[(k - 1 + offset) as usize] < furthest[(k + 1 + offset) as usize]);
+      let mut x = match down {
+        true => furthest[(k + 1 + offset) as usize],
+        false => furthest[(k - 1 + offset) as usize] + 1,
+      };
+      let mut y = x - k;
+      while x < n && y < m && old.get(x as usize) == new.get(y as usize) {
+        x += 1;
+        y += 1;
+      }
+      furthest[(k + offset) as usize] = x;
+
+      if x >= n && y >= m {
+        trace.push(furthest[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        return Some(backtrack(&trace, n, m));
+      }
+    }
+    trace.push(furthest[(offset - d) as usize..=(offset + d) as usize].to_vec());
+  }
+  None
+}
+
+/// Walk the rounds of the search back from the end of both lists, building the edits in reverse
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<Edit> {
+  let mut edits = Vec::new();
+  let (mut x, mut y) = (n, m);
+  for d in (1..trace.len() as isize).rev() {
+    // The furthest points after the previous round, with diagonal k stored at k + d - 1
+    let prev = &trace[d as usize - 1];
+    let at = |k: isize| prev[(k + d - 1) as usize];
+    let k = x - y;
+    let prev_k = match k == -d || (k != d && at(k - 1) < at(k + 1)) {
+      true => k + 1,
+      false => k - 1,
+    };
+    let prev_x = at(prev_k);
+    let prev_y = prev_x - prev_k;
+
+    while x > prev_x && y > prev_y {
+      edits.push(Edit::Keep);
+      x -= 1;
+      y -= 1;
+    }
+    match x == prev_x {
+      true => edits.push(Edit::Insert),
+      false => edits.push(Edit::Remove),
+    }
+    x = prev_x;
+    y = prev_y;
+  }
+  // Whatever is left before the first edit is the same in both lists
+  edits.extend((0..x).map(|_| Edit::Keep));
+  edits.reverse();
+  edits
+}
+
+/// The id of each record in the list, or None if any are missing or repeated
+fn record_ids<'a, L, T>(list: &'a L) -> Option<Vec<String>>
+where
+  L: ListLike<T>,
+  T: Patchwork<'a> + 'a,
+{
+  let mut seen = HashSet::new();
+  let mut ids = Vec::with_capacity(list.len());
+  for idx in 0..list.len() {
+    let id = list.get(idx)?.get_id()?;
+    if !seen.insert(id.clone()) {
+      return None;
+    }
+    ids.push(id);
+  }
+  Some(ids)
+}
+
+/// Lists are diffed by `diff_list`, so their elements only need to be comparable
+macro_rules! patchable_list {
+  ($($list:ident),*) => {
+    $(
+      impl<'a, T> Patchable<'a> for $list<T>
+      where
+        T: PartialEq + Debug + Serialize + DeserializeOwned,
+      {
+        fn diff_value(
+          &'a self,
+          name: &str,
+          other: &'a Self,
+        ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+          diff_list(name, self, other)
+        }
+
+        fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+          match action.get_action() {
+            Action::Steps => primitives::apply_steps(self, action),
+            _ => apply_list(self, action),
+          }
+        }
+      }
+    )*
+  };
+}
+
+patchable_list!(Vec, VecDeque);
+
+/// The functions for `#[protean(with = "protean::impls::list::keyed")]` on a list of records
+///
+/// The records are matched up by their ids with `diff_keyed`, rather than compared as whole values.
+pub mod keyed {
+  use super::*;
+
+  pub fn diff<'a, L, T>(
+    name: &str,
+    value: &'a L,
+    other: &'a L,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError>
+  where
+    L: ListLike<T> + Serialize,
+    T: Patchwork<'a> + 'a,
+  {
+    diff_keyed(name, value, other)
+  }
+
+  pub fn apply<'a, L, T>(
+    value: &mut L,
+    action: PatchAction,
+  ) -> Result<PatchAction<'a>, ProteanError>
+  where
+    L: ListLike<T> + Serialize + DeserializeOwned + Default,
+    T: Serialize + DeserializeOwned,
+  {
+    apply_list(value, action)
+  }
 }
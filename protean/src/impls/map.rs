@@ -0,0 +1,268 @@
+//! Patchwork implementations for sets of key/value pairs
+
+use super::*;
+
+use serde::de::{self, IgnoredAny, MapAccess, Visitor};
+use std::collections::BTreeMap;
+
+pub use crate::patch::MapAction;
+
+/// The operations needed to apply a MapAction directly to a map
+pub trait MapLike<K, V> {
+  fn get(&self, key: &K) -> Option<&V>;
+
+  fn get_mut(&mut self, key: &K) -> Option<&mut V>;
+
+  fn insert(&mut self, key: K, value: V) -> Option<V>;
+
+  fn remove(&mut self, key: &K) -> Option<V>;
+}
+
+impl<K: Eq + Hash, V> MapLike<K, V> for HashMap<K, V> {
+  fn get(&self, key: &K) -> Option<&V> {
+    HashMap::get(self, key)
+  }
+
+  fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+    HashMap::get_mut(self, key)
+  }
+
+  fn insert(&mut self, key: K, value: V) -> Option<V> {
+    HashMap::insert(self, key, value)
+  }
+
+  fn remove(&mut self, key: &K) -> Option<V> {
+    HashMap::remove(self, key)
+  }
+}
+
+impl<K: Ord, V> MapLike<K, V> for BTreeMap<K, V> {
+  fn get(&self, key: &K) -> Option<&V> {
+    BTreeMap::get(self, key)
+  }
+
+  fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+    BTreeMap::get_mut(self, key)
+  }
+
+  fn insert(&mut self, key: K, value: V) -> Option<V> {
+    BTreeMap::insert(self, key, value)
+  }
+
+  fn remove(&mut self, key: &K) -> Option<V> {
+    BTreeMap::remove(self, key)
+  }
+}
+
+/// Apply an action to a HashMap or BTreeMap, returning the action that will undo it
+///
+/// Map actions only touch the entry for their key, with a nested patch in an Update being applied to
+/// the existing value. Inserting a key that is already there is an Update when the patch allows
+/// upserts, and a DuplicateKey error otherwise. Anything else is treated as a whole value by
+/// `apply_value`.
+pub fn apply_map<'a, M, K, V>(
+  map: &mut M,
+  action: PatchAction,
+) -> Result<PatchAction<'a>, ProteanError>
+where
+  M: MapLike<K, V> + Serialize + DeserializeOwned + Default,
+  K: DeserializeOwned,
+  V: Serialize + DeserializeOwned,
+{
+  let act = match action.get_action() {
+    Action::Map(act) => act.clone(),
+    _ => return primitives::apply_value(map, action),
+  };
+  let name = action.get_name();
+  let owned = |value| PatchValue::Value(Box::new(OwnedValue::new(name.clone(), value)));
+  let undo = |act, value| PatchAction::from_value(Action::Map(act), value, None);
+
+  Ok(match act {
+    MapAction::Insert(key) => {
+      let id = parse_key::<K>(&key)?;
+      let value: V = serde_json::from_value(action.get_value().as_json()?)?;
+      match map.get(&id) {
+        Some(_) if !action.get_options().allow_upsert() => return Err(ProteanError::DuplicateKey),
+        Some(old) => {
+          let old = serde_json::to_value(old)?;
+          map.insert(id, value);
+          undo(MapAction::Update(key), owned(old))
+        }
+        None => {
+          map.insert(id, value);
+          undo(MapAction::Delete(key), owned(serde_json::Value::Null))
+        }
+      }
+    }
+    MapAction::Update(key) => {
+      let id = parse_key::<K>(&key)?;
+      let entry = map
+        .get_mut(&id)
+        .ok_or_else(|| ProteanError::KeyNotFound(key.clone()))?;
+      let mut json = serde_json::to_value(&*entry)?;
+      let reverse = match action.into_value() {
+        PatchValue::Value(value) => owned(std::mem::replace(&mut json, value.as_json()?)),
+        PatchValue::Patch(patch) => PatchValue::Patch(json::apply_patch(&mut json, patch)?),
+        PatchValue::Steps(_, _) => return Err(ProteanError::InvalidPatchType),
+      };
+      *entry = serde_json::from_value(json)?;
+      undo(MapAction::Update(key), reverse)
+    }
+    MapAction::Delete(key) => {
+      let old = map
+        .remove(&parse_key::<K>(&key)?)
+        .ok_or_else(|| ProteanError::KeyNotFound(key.clone()))?;
+      undo(MapAction::Insert(key), owned(serde_json::to_value(old)?))
+    }
+  })
+}
+
+/// Compare two maps, returning the action that turns old into new or None if they are the same
+///
+/// Keys only in old are deleted and those only in new are inserted, each sorted by key so the same
+/// change always gives the same patch. Values under the same key are diffed by their own type, and
+/// collected into a nested patch named by the keys. This is the same patch `json::diff` gives for the
+/// map's JSON.
+pub fn diff_map<'a, M, K, V>(
+  name: &str,
+  old: &'a M,
+  new: &'a M,
+) -> Result<Option<PatchAction<'a>>, ProteanError>
+where
+  M: MapLike<K, V>,
+  &'a M: IntoIterator<Item = (&'a K, &'a V)>,
+  K: Serialize + 'a,
+  V: Patchable<'a> + 'a,
+{
+  let entry = |act: MapAction, value: serde_json::Value| {
+    PatchAction::new(
+      Action::Map(act),
+      OwnedValue::new(name.to_string(), value),
+      None,
+    )
+  };
+
+  let mut deleted = Vec::new();
+  let mut changed = Vec::new();
+  for (id, value) in old {
+    let key = key_string(id)?;
+    match new.get(id) {
+      None => deleted.push(key),
+      Some(other) => {
+        if let Some(action) = value.diff_value(&key, other)? {
+          changed.push(action);
+        }
+      }
+    }
+  }
+
+  let mut inserted = Vec::new();
+  for (id, value) in new {
+    if old.get(id).is_none() {
+      inserted.push((key_string(id)?, serde_json::to_value(value)?));
+    }
+  }
+  deleted.sort();
+  changed.sort_by_cached_key(PatchAction::get_name);
+  inserted.sort_by(|left, right| left.0.cmp(&right.0));
+
+  let mut steps: Vec<PatchAction<'a>> = deleted
+    .into_iter()
+    .map(|key| entry(MapAction::Delete(key), serde_json::Value::Null))
+    .collect();
+  if !changed.is_empty() {
+    let mut patch = Patch::new(name.to_string());
+    for action in changed {
+      patch.add_action(action)?;
+    }
+    steps.push(PatchAction::from_patch(name.to_string(), patch));
+  }
+  for (key, value) in inserted {
+    steps.push(entry(MapAction::Insert(key), value));
+  }
+
+  Ok(match steps.is_empty() {
+    true => None,
+    false => Some(PatchAction::from_steps(name.to_string(), steps)),
+  })
+}
+
+/// The key as serde_json writes it for an object, which `parse_key` reads back
+fn key_string<K: Serialize>(key: &K) -> Result<String, ProteanError> {
+  Ok(match serde_json::to_value(key)? {
+    serde_json::Value::String(key) => key,
+    key => key.to_string(),
+  })
+}
+
+macro_rules! patchable_map {
+  ($($map:ident where K: $($bound:ident)+),*) => {
+    $(
+      impl<'a, K, V> Patchable<'a> for $map<K, V>
+      where
+        K: $($bound +)+ Debug + Serialize + DeserializeOwned + 'a,
+        V: Patchable<'a> + DeserializeOwned + 'a,
+      {
+        fn diff_value(
+          &'a self,
+          name: &str,
+          other: &'a Self,
+        ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+          diff_map(name, self, other)
+        }
+
+        fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+          match action.get_action() {
+            Action::Steps => primitives::apply_steps(self, action),
+            _ => apply_map(self, action),
+          }
+        }
+      }
+    )*
+  };
+}
+
+patchable_map!(HashMap where K: Eq Hash, BTreeMap where K: Ord);
+
+/// Turn the key from a MapAction back into the map's key type
+///
+/// The key is read the same way serde_json reads the keys of an object, so numbers and other
+/// non-string keys work as well.
+pub fn parse_key<K: DeserializeOwned>(key: &str) -> Result<K, ProteanError> {
+  struct KeyOnly<K>(K);
+
+  impl<'de, K: Deserialize<'de>> Deserialize<'de> for KeyOnly<K> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+      D: serde::Deserializer<'de>,
+    {
+      struct KeyVisitor<K>(std::marker::PhantomData<K>);
+
+      impl<'de, K: Deserialize<'de>> Visitor<'de> for KeyVisitor<K> {
+        type Value = KeyOnly<K>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+          write!(f, "an object with a single key")
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+        where
+          A: MapAccess<'de>,
+        {
+          let key = map
+            .next_key()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+          map.next_value::<IgnoredAny>()?;
+          Ok(KeyOnly(key))
+        }
+      }
+
+      deserializer.deserialize_map(KeyVisitor(std::marker::PhantomData))
+    }
+  }
+
+  let mut object = serde_json::Map::new();
+  object.insert(key.to_string(), serde_json::Value::Null);
+  let KeyOnly(key) = serde_json::from_value(serde_json::Value::Object(object))?;
+  Ok(key)
+}
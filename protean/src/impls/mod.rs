@@ -10,5 +10,8 @@ pub mod list;
 pub mod map;
 pub mod object;
 pub mod primitives;
+pub mod set;
+pub mod tuple;
+pub mod wrapper;
 
 pub mod json;
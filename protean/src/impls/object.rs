@@ -1,3 +1,62 @@
 //! Implementation of an object
 //!
 //! These are the basic struct/enums in Rust, essentially anything that contains a field.
+
+use super::*;
+
+/// Apply an action to a field holding another Patchwork struct
+///
+/// Nested patches are handed to the struct's own `apply`, while anything else is treated as a plain
+/// value and replaced as a whole.
+pub fn apply_nested<'a, T>(
+  value: &mut T,
+  action: PatchAction,
+) -> Result<PatchAction<'a>, ProteanError>
+where
+  T: Patchwork<'a> + Default,
+{
+  if let PatchValue::Value(_) = action.get_value() {
+    return primitives::apply_value(value, action);
+  }
+
+  let name = action.get_name();
+  let reverse = value.apply(action.into_value().into_patch()?)?;
+  Ok(PatchAction::from_patch(name, reverse))
+}
+
+/// Compare two nested Patchwork structs, returning their differences as a single nested patch
+pub fn diff_nested<'a, T>(
+  name: &str,
+  value: &'a T,
+  other: &'a T,
+) -> Result<Option<PatchAction<'a>>, ProteanError>
+where
+  T: Patchwork<'a>,
+{
+  let patch = value.diff(other)?;
+  Ok(match patch.is_empty() {
+    true => None,
+    false => Some(PatchAction::from_patch(name.to_string(), patch)),
+  })
+}
+
+/// Apply an action to a nested Patchwork struct that is also Patchable
+///
+/// This is `apply_nested` for structs without a Default, so a Reset is refused instead.
+pub fn apply_object<'a, T>(
+  value: &mut T,
+  action: PatchAction,
+) -> Result<PatchAction<'a>, ProteanError>
+where
+  T: Patchwork<'a> + Patchable<'a>,
+{
+  match action.get_value() {
+    PatchValue::Patch(_) => {
+      let name = action.get_name();
+      let reverse = value.apply(action.into_value().into_patch()?)?;
+      Ok(PatchAction::from_patch(name, reverse))
+    }
+    PatchValue::Steps(_, _) => primitives::apply_steps(value, action),
+    PatchValue::Value(_) => primitives::apply_whole(value, action),
+  }
+}
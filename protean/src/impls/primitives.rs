@@ -1 +1,223 @@
 //! Implementations for basic primitive fields
+
+use super::*;
+
+/// Apply an action to any serializable value, returning the action that will undo it
+///
+/// The value is converted to JSON so it can be modified by `json::apply_action`, then converted back
+/// into its own type. The field is only updated if the action succeeds. Reset is handled here, as it
+/// needs the type's Default which is lost once the value is JSON.
+pub fn apply_value<'a, T>(
+  value: &mut T,
+  action: PatchAction,
+) -> Result<PatchAction<'a>, ProteanError>
+where
+  T: Serialize + DeserializeOwned + Default,
+{
+  if let Action::Reset = action.get_action() {
+    let old = serde_json::to_value(std::mem::take(value))?;
+    return Ok(PatchAction::new(
+      Action::Set,
+      OwnedValue::new(action.get_name(), old),
+      None,
+    ));
+  }
+  apply_whole(value, action)
+}
+
+/// Apply an action to a serializable value that has no Default
+///
+/// This is `apply_value` without the special handling of Reset, which is left to JSON to refuse.
+pub fn apply_whole<'a, T>(
+  value: &mut T,
+  action: PatchAction,
+) -> Result<PatchAction<'a>, ProteanError>
+where
+  T: Serialize + DeserializeOwned,
+{
+  let mut json = serde_json::to_value(&*value)?;
+  let undo = json::apply_action(&mut json, action)?;
+  *value = serde_json::from_value(json)?;
+  Ok(undo)
+}
+
+/// Apply an action to a Patchable value, handing it the steps of a sequence one at a time
+///
+/// The steps that already ran are undone if a later one fails.
+pub fn apply_steps<'a, T>(
+  value: &mut T,
+  action: PatchAction,
+) -> Result<PatchAction<'a>, ProteanError>
+where
+  T: Patchable<'a> + ?Sized,
+{
+  if action.get_action() != &Action::Steps {
+    return value.apply_value(action);
+  }
+
+  let name = action.get_name();
+  let mut undo = Vec::new();
+  for step in action.into_steps() {
+    match value.apply_value(step) {
+      Ok(step) => undo.push(step),
+      Err(err) => {
+        for step in undo.into_iter().rev() {
+          value.apply_value(step)?;
+        }
+        return Err(err);
+      }
+    }
+  }
+  undo.reverse();
+  Ok(PatchAction::from_steps(name, undo))
+}
+
+/// Apply each action in a patch with the given function, returning the actions that will undo them
+///
+/// The actions inherit the options of the patch, and any with an expected hash are checked against
+/// the JSON `current` gives for their field first. If one fails, the actions that were already
+/// applied are undone in reverse order before the error is returned.
+pub fn apply_each<'a, T, C, F>(
+  target: &mut T,
+  patch: Patch,
+  mut current: C,
+  mut apply: F,
+) -> Result<Vec<PatchAction<'a>>, ProteanError>
+where
+  T: ?Sized,
+  C: FnMut(&T, &str) -> Result<serde_json::Value, ProteanError>,
+  F: FnMut(&mut T, &str, PatchAction) -> Result<PatchAction<'a>, ProteanError>,
+{
+  let options = patch.get_options().cloned().unwrap_or_default();
+  let mut undo: Vec<PatchAction<'a>> = Vec::new();
+  for mut action in patch.into_actions() {
+    let name = action.get_name();
+    action.inherit_options(&options);
+    let result = match action.get_expected() {
+      Some(_) => current(target, &name),
+      None => Ok(serde_json::Value::Null),
+    }
+    .and_then(|found| action.check_expected(&found, options.on_mismatch()))
+    .and_then(|ok| match ok {
+      true => apply(target, &name, action)
+        .map_err(|err| err.within(&name))
+        .map(Some),
+      false => Ok(None),
+    });
+
+    match result {
+      Ok(step) => undo.extend(step),
+      Err(err) => {
+        for step in undo.into_iter().rev() {
+          apply(target, &step.get_name(), step)?;
+        }
+        return Err(err);
+      }
+    }
+  }
+  Ok(undo)
+}
+
+/// Apply a nested patch to the parts of a value, such as the elements of a tuple or the variant of a
+/// Result, returning the action that will undo it
+///
+/// Each action in the patch is named after the part it changes, and is handed to `apply` along with
+/// that name. Any parts that were already changed are rolled back if one of them fails.
+pub fn apply_parts<'a, T, F>(
+  value: &mut T,
+  action: PatchAction,
+  apply: F,
+) -> Result<PatchAction<'a>, ProteanError>
+where
+  T: Serialize,
+  F: FnMut(&mut T, &str, PatchAction) -> Result<PatchAction<'a>, ProteanError>,
+{
+  let name = action.get_name();
+  let current = |value: &T, part: &str| {
+    let json = serde_json::to_value(value)?;
+    Ok(json::child(&json, part).cloned().unwrap_or_default())
+  };
+  let undo = apply_each(value, action.into_value().into_patch()?, current, apply)?;
+
+  let mut reverse = Patch::new(name.clone());
+  for step in undo {
+    reverse.add_action(step)?;
+  }
+  Ok(PatchAction::from_patch(name, reverse))
+}
+
+/// Compare two values as a whole, setting the field to the other one when they differ
+pub fn diff_whole<'a, T>(
+  name: &str,
+  value: &'a T,
+  other: &'a T,
+) -> Result<Option<PatchAction<'a>>, ProteanError>
+where
+  T: Patchable<'a> + PartialEq + Send + Sync + 'static,
+{
+  Ok(match value == other {
+    true => None,
+    false => Some(PatchAction::new(Action::Set, Field::new(name, other), None)),
+  })
+}
+
+/// Values that are only ever replaced as a whole
+macro_rules! patchable_primitive {
+  ($($ty:ty),* $(,)?) => {
+    $(
+      impl<'a> Patchable<'a> for $ty {
+        fn diff_value(
+          &'a self,
+          name: &str,
+          other: &'a Self,
+        ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+          diff_whole(name, self, other)
+        }
+
+        fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+          apply_value(self, action)
+        }
+      }
+    )*
+  };
+}
+
+patchable_primitive!(
+  bool,
+  char,
+  String,
+  (),
+  uuid::Uuid,
+  i8,
+  i16,
+  i32,
+  i64,
+  i128,
+  isize,
+  u8,
+  u16,
+  u32,
+  u64,
+  u128,
+  usize,
+  f32,
+  f64,
+);
+
+/// A borrowed str can be diffed, but there is nothing owned to write a patched value back into
+impl<'a> Patchable<'a> for &'static str {
+  fn diff_value(
+    &'a self,
+    name: &str,
+    other: &'a Self,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    diff_whole(name, self, other)
+  }
+
+  fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+    Err(ProteanError::UnsupportedAction(format!(
+      "{:?} on a borrowed str",
+      action.get_action()
+    )))
+  }
+}
@@ -0,0 +1,178 @@
+//! Patchwork implementations for unordered sets of unique values
+
+use super::*;
+
+use std::collections::BTreeSet;
+
+pub use crate::patch::SetAction;
+
+/// The operations needed to apply a SetAction directly to a set
+pub trait SetLike<T> {
+  fn contains(&self, value: &T) -> bool;
+
+  /// Add the value, returning false if it was already there
+  fn insert(&mut self, value: T) -> bool;
+
+  /// Take the value out, returning false if it wasn't there
+  fn remove(&mut self, value: &T) -> bool;
+
+  fn values(&self) -> Vec<&T>;
+}
+
+impl<T: Eq + Hash> SetLike<T> for HashSet<T> {
+  fn contains(&self, value: &T) -> bool {
+    HashSet::contains(self, value)
+  }
+
+  fn insert(&mut self, value: T) -> bool {
+    HashSet::insert(self, value)
+  }
+
+  fn remove(&mut self, value: &T) -> bool {
+    HashSet::remove(self, value)
+  }
+
+  fn values(&self) -> Vec<&T> {
+    self.iter().collect()
+  }
+}
+
+impl<T: Ord> SetLike<T> for BTreeSet<T> {
+  fn contains(&self, value: &T) -> bool {
+    BTreeSet::contains(self, value)
+  }
+
+  fn insert(&mut self, value: T) -> bool {
+    BTreeSet::insert(self, value)
+  }
+
+  fn remove(&mut self, value: &T) -> bool {
+    BTreeSet::remove(self, value)
+  }
+
+  fn values(&self) -> Vec<&T> {
+    self.iter().collect()
+  }
+}
+
+/// Apply an action to a HashSet or BTreeSet, returning the action that will undo it
+///
+/// Adding a value that is already there does nothing when the patch allows upserts, and is a
+/// DuplicateKey error otherwise. Removing a value that isn't there is a KeyNotFound error. Anything
+/// else is treated as a whole value by `apply_value`.
+pub fn apply_set<'a, S, T>(
+  set: &mut S,
+  action: PatchAction,
+) -> Result<PatchAction<'a>, ProteanError>
+where
+  S: SetLike<T> + Serialize + DeserializeOwned + Default,
+  T: DeserializeOwned,
+{
+  let act = match action.get_action() {
+    Action::Members(act) => act.clone(),
+    _ => return primitives::apply_value(set, action),
+  };
+  let json = action.get_value().as_json()?;
+  let value: T = serde_json::from_value(json.clone())?;
+  let undo = |act, value| PatchAction::new(act, OwnedValue::new(action.get_name(), value), None);
+
+  Ok(match act {
+    SetAction::Add if set.contains(&value) => match action.get_options().allow_upsert() {
+      true => undo(Action::Null, serde_json::Value::Null),
+      false => return Err(ProteanError::DuplicateKey),
+    },
+    SetAction::Add => {
+      set.insert(value);
+      undo(Action::Members(SetAction::Remove), json)
+    }
+    SetAction::Remove => match set.remove(&value) {
+      true => undo(Action::Members(SetAction::Add), json),
+      false => return Err(ProteanError::KeyNotFound(json.to_string())),
+    },
+  })
+}
+
+/// Compare two sets, returning the steps that turn old into new or None if they are the same
+///
+/// Values only in old are removed and those only in new are added. The removes come first, and each
+/// group is sorted by the serialized value so the same change always gives the same patch, whatever
+/// order the set iterates in.
+pub fn diff_set<'a, S, T>(
+  name: &str,
+  old: &S,
+  new: &S,
+) -> Result<Option<PatchAction<'a>>, ProteanError>
+where
+  S: SetLike<T>,
+  T: Serialize,
+{
+  let sorted = |from: &S, to: &S| -> Result<Vec<serde_json::Value>, ProteanError> {
+    let mut values = Vec::new();
+    for value in from.values() {
+      if !to.contains(value) {
+        values.push(serde_json::to_value(value)?);
+      }
+    }
+    values.sort_by_cached_key(|value| value.to_string());
+    Ok(values)
+  };
+
+  let mut steps = Vec::new();
+  for (act, values) in [
+    (SetAction::Remove, sorted(old, new)?),
+    (SetAction::Add, sorted(new, old)?),
+  ] {
+    for value in values {
+      steps.push(PatchAction::new(
+        Action::Members(act.clone()),
+        OwnedValue::new(name.to_string(), value),
+        None,
+      ));
+    }
+  }
+
+  Ok(match steps.is_empty() {
+    true => None,
+    false => Some(PatchAction::from_steps(name.to_string(), steps)),
+  })
+}
+
+impl<'a, T> Patchable<'a> for HashSet<T>
+where
+  T: Eq + Hash + Debug + Serialize + DeserializeOwned,
+{
+  fn diff_value(
+    &'a self,
+    name: &str,
+    other: &'a Self,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    diff_set(name, self, other)
+  }
+
+  fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+    match action.get_action() {
+      Action::Steps => primitives::apply_steps(self, action),
+      _ => apply_set(self, action),
+    }
+  }
+}
+
+impl<'a, T> Patchable<'a> for BTreeSet<T>
+where
+  T: Ord + Debug + Serialize + DeserializeOwned,
+{
+  fn diff_value(
+    &'a self,
+    name: &str,
+    other: &'a Self,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    diff_set(name, self, other)
+  }
+
+  fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+    match action.get_action() {
+      Action::Steps => primitives::apply_steps(self, action),
+      _ => apply_set(self, action),
+    }
+  }
+}
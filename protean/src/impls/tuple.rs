@@ -0,0 +1,106 @@
+//! Implementations for tuples and fixed size arrays
+//!
+//! Neither can change length, so they are diffed element by element into a nested patch with each
+//! action named after the index it changes.
+
+use super::*;
+
+use primitives::{apply_parts, apply_steps, apply_whole};
+
+/// Wrap the changes to each element into a single nested patch
+fn from_elements<'a>(
+  name: &str,
+  elements: Vec<Option<PatchAction<'a>>>,
+) -> Result<Option<PatchAction<'a>>, ProteanError> {
+  let mut patch = Patch::new(name.to_string());
+  for action in elements.into_iter().flatten() {
+    patch.add_action(action)?;
+  }
+  Ok(match patch.is_empty() {
+    true => None,
+    false => Some(PatchAction::from_patch(name.to_string(), patch)),
+  })
+}
+
+fn index(part: &str) -> Result<usize, ProteanError> {
+  part.parse().map_err(|_| ProteanError::FieldNotFound)
+}
+
+macro_rules! patchable_tuple {
+  ($($idx:tt $ty:ident),+) => {
+    impl<'a, $($ty),+> Patchable<'a> for ($($ty,)+)
+    where
+      $($ty: Patchable<'a> + DeserializeOwned,)+
+    {
+      fn diff_value(
+        &'a self,
+        name: &str,
+        other: &'a Self,
+      ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+        from_elements(
+          name,
+          vec![$(self.$idx.diff_value(stringify!($idx), &other.$idx)?),+],
+        )
+      }
+
+      fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+        match action.get_value() {
+          PatchValue::Steps(_, _) => apply_steps(self, action),
+          PatchValue::Patch(_) => apply_parts(self, action, |tuple, part, action| {
+            match index(part)? {
+              $($idx => apply_steps(&mut tuple.$idx, action),)+
+              idx => Err(ProteanError::IndexOutOfRange(idx)),
+            }
+          }),
+          PatchValue::Value(_) => apply_whole(self, action),
+        }
+      }
+    }
+  };
+}
+
+patchable_tuple!(0 T0);
+patchable_tuple!(0 T0, 1 T1);
+patchable_tuple!(0 T0, 1 T1, 2 T2);
+patchable_tuple!(0 T0, 1 T1, 2 T2, 3 T3);
+patchable_tuple!(0 T0, 1 T1, 2 T2, 3 T3, 4 T4);
+patchable_tuple!(0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5);
+patchable_tuple!(0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5, 6 T6);
+patchable_tuple!(0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5, 6 T6, 7 T7);
+patchable_tuple!(0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5, 6 T6, 7 T7, 8 T8);
+patchable_tuple!(0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5, 6 T6, 7 T7, 8 T8, 9 T9);
+patchable_tuple!(0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5, 6 T6, 7 T7, 8 T8, 9 T9, 10 T10);
+patchable_tuple!(0 T0, 1 T1, 2 T2, 3 T3, 4 T4, 5 T5, 6 T6, 7 T7, 8 T8, 9 T9, 10 T10, 11 T11);
+
+/// Serde only implements arrays up to a length of 32, which limits the sizes that can be used here
+impl<'a, T, const N: usize> Patchable<'a> for [T; N]
+where
+  T: Patchable<'a>,
+  [T; N]: Serialize + DeserializeOwned,
+{
+  fn diff_value(
+    &'a self,
+    name: &str,
+    other: &'a Self,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    let mut elements = Vec::with_capacity(N);
+    for (idx, (value, other)) in self.iter().zip(other.iter()).enumerate() {
+      elements.push(value.diff_value(&idx.to_string(), other)?);
+    }
+    from_elements(name, elements)
+  }
+
+  fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+    match action.get_value() {
+      PatchValue::Steps(_, _) => apply_steps(self, action),
+      PatchValue::Patch(_) => apply_parts(self, action, |array, part, action| {
+        let idx = index(part)?;
+        match array.get_mut(idx) {
+          Some(value) => apply_steps(value, action),
+          None => Err(ProteanError::IndexOutOfRange(idx)),
+        }
+      }),
+      PatchValue::Value(_) => apply_whole(self, action),
+    }
+  }
+}
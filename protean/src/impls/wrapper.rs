@@ -0,0 +1,178 @@
+//! Implementations for values that wrap a single other value
+//!
+//! Option and the smart pointers serialize as the value they hold, so their patches are the same as
+//! the inner value's. Result keeps its variant as a key, so changes within the same variant are a
+//! nested patch named "Ok" or "Err".
+
+use super::*;
+
+use primitives::{apply_parts, apply_steps, apply_whole};
+use std::{rc::Rc, sync::Arc};
+
+/// Replace the whole value with a copy of the other one, unless they are already the same
+fn set_whole<'a, T: Serialize + PartialEq + ?Sized>(
+  name: &str,
+  value: &T,
+  other: &T,
+) -> Result<Option<PatchAction<'a>>, ProteanError> {
+  if value == other {
+    return Ok(None);
+  }
+  Ok(Some(PatchAction::new(
+    Action::Set,
+    OwnedValue::new(name.to_string(), serde_json::to_value(other)?),
+    None,
+  )))
+}
+
+/// Going between None and Some sets the whole value, while two Somes are diffed by the inner type
+///
+/// When applying, a Set or Reset replaces the whole Option. Everything else changes the value inside,
+/// and is an InvalidPatchType error when there is nothing there, apart from a Clear which leaves None
+/// as it is.
+impl<'a, T> Patchable<'a> for Option<T>
+where
+  T: Patchable<'a> + PartialEq + DeserializeOwned,
+{
+  fn diff_value(
+    &'a self,
+    name: &str,
+    other: &'a Self,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    match (self, other) {
+      (Some(value), Some(other)) => value.diff_value(name, other),
+      _ => set_whole(name, self, other),
+    }
+  }
+
+  fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+    let whole = match (action.get_action(), action.get_value()) {
+      (Action::Steps, _) => return apply_steps(self, action),
+      (Action::Reset, _) | (Action::Null, _) => true,
+      (Action::Set, PatchValue::Value(_)) => true,
+      _ => false,
+    };
+
+    match self {
+      _ if whole => primitives::apply_value(self, action),
+      Some(value) => value.apply_value(action),
+      // Like a JSON null, there is nothing left to clear
+      None if *action.get_action() == Action::Clear => Ok(PatchAction::new(
+        Action::Null,
+        OwnedValue::new(action.get_name(), serde_json::Value::Null),
+        None,
+      )),
+      None => Err(ProteanError::InvalidPatchType),
+    }
+  }
+}
+
+/// Changing variant sets the whole value, while two of the same variant are diffed by their contents
+///
+/// Result has no Default, so it can't be Reset.
+impl<'a, T, E> Patchable<'a> for Result<T, E>
+where
+  T: Patchable<'a> + PartialEq + DeserializeOwned,
+  E: Patchable<'a> + PartialEq + DeserializeOwned,
+{
+  fn diff_value(
+    &'a self,
+    name: &str,
+    other: &'a Self,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    let inner = match (self, other) {
+      (Ok(value), Ok(other)) => value.diff_value("Ok", other)?,
+      (Err(value), Err(other)) => value.diff_value("Err", other)?,
+      _ => return set_whole(name, self, other),
+    };
+
+    Ok(match inner {
+      None => None,
+      Some(action) => {
+        let mut patch = Patch::new(name.to_string());
+        patch.add_action(action)?;
+        Some(PatchAction::from_patch(name.to_string(), patch))
+      }
+    })
+  }
+
+  fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+    match action.get_value() {
+      PatchValue::Steps(_, _) => apply_steps(self, action),
+      PatchValue::Patch(_) => {
+        apply_parts(self, action, |result, part, action| match (part, result) {
+          ("Ok", Ok(value)) => apply_steps(value, action),
+          ("Err", Err(value)) => apply_steps(value, action),
+          _ => Err(ProteanError::FieldNotFound),
+        })
+      }
+      PatchValue::Value(_) => apply_whole(self, action),
+    }
+  }
+}
+
+impl<'a, T: Patchable<'a>> Patchable<'a> for Box<T> {
+  fn diff_value(
+    &'a self,
+    name: &str,
+    other: &'a Self,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    (**self).diff_value(name, other)
+  }
+
+  fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+    (**self).apply_value(action)
+  }
+}
+
+/// A shared value is cloned before it is changed, so the other owners keep the original
+///
+/// Rc isn't Sync, so a struct using `Field` for its values should give it `&*self.value` instead.
+impl<'a, T: Patchable<'a> + Clone> Patchable<'a> for Rc<T> {
+  fn diff_value(
+    &'a self,
+    name: &str,
+    other: &'a Self,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    (**self).diff_value(name, other)
+  }
+
+  fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+    Rc::make_mut(self).apply_value(action)
+  }
+}
+
+/// A shared value is cloned before it is changed, so the other owners keep the original
+impl<'a, T: Patchable<'a> + Clone> Patchable<'a> for Arc<T> {
+  fn diff_value(
+    &'a self,
+    name: &str,
+    other: &'a Self,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    (**self).diff_value(name, other)
+  }
+
+  fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+    Arc::make_mut(self).apply_value(action)
+  }
+}
+
+/// A borrowed value is usually a str or slice rather than something Patchable, so it is compared as a
+/// whole. Applying takes ownership of the value first.
+impl<'a, 'c, B> Patchable<'a> for Cow<'c, B>
+where
+  B: ToOwned + Serialize + Debug + PartialEq + ?Sized,
+  B::Owned: Patchable<'a>,
+{
+  fn diff_value(
+    &'a self,
+    name: &str,
+    other: &'a Self,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    set_whole(name, &**self, &**other)
+  }
+
+  fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+    self.to_mut().apply_value(action)
+  }
+}
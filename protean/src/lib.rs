@@ -9,8 +9,7 @@
 //! - **Has changed** Allows for more granular equality testing rather than "Yes"/"No". Changes on
 //!   fields considered unimportant can be ignored.
 //!
-//! TODO:
-//! - Make derive in Protean
+//! Structs with named fields can `#[derive(Patchwork)]`, as long as each field is Patchable.
 
 /*
 
@@ -18,12 +17,24 @@ workbook - try_into<Patch<Db>>
 
 */
 
+pub mod compose;
+
 pub mod error;
 
+pub mod field;
+
+pub mod hash;
+
 pub mod impls;
 
+pub mod merge;
+
 pub mod patch;
 
+pub mod rebase;
+
+pub mod serde_patchwork;
+
 pub mod traits;
 
 mod local {
@@ -31,18 +42,35 @@ mod local {
 
   pub use std::{
     borrow::Cow,
-    collections::{hash_map::Entry, HashMap, HashSet},
+    collections::{btree_map::Entry, BTreeMap, HashMap, HashSet},
     fmt::{Debug, Display},
     hash::Hash,
+    str::FromStr,
   };
 
-  pub use serde::{Deserialize, Serialize};
+  pub use serde::{de::DeserializeOwned, Deserialize, Serialize};
+}
+
+#[cfg(feature = "protean_derive")]
+pub use protean_derive::Patchwork;
+
+/// Crates used by the code the derive writes, so models don't need to depend on them directly
+#[doc(hidden)]
+pub mod __private {
+  pub use serde;
+  pub use serde_json;
 }
 
 pub mod prelude {
   pub use super::*;
 
   pub use error::ProteanError;
-  pub use patch::{Action, Patch, PatchAction, PatchOptions};
-  pub use traits::{Patchwork, Patchworthy};
+  pub use field::{Field, FieldName};
+  pub use merge::Conflict;
+  pub use patch::{
+    Action, ListAction, MapAction, OnMismatch, OwnedValue, Patch, PatchAction, PatchBuf,
+    PatchOptions, PatchValue, SetAction,
+  };
+  pub use serde_patchwork::Serde;
+  pub use traits::{Patchable, Patchwork, Patchworthy};
 }
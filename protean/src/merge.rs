@@ -0,0 +1,416 @@
+//! Combining patches made against the same model
+//!
+//! Patches that touch different fields can be combined into one, so several changes can be sent or
+//! applied together. When two patches change the same field in different ways, the result is a list
+//! of conflicts instead of picking a winner.
+//!
+//! When the common ancestor of both patches is known, a three way merge can look inside the values that
+//! both sides replaced, so edits to different keys of a map or different parts of a list still merge.
+
+use crate::{impls::json, local::*};
+
+use serde_json::Value;
+
+/// A field that two patches both change, but in different ways
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+  feature = "serde_support",
+  derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Conflict {
+  /// Dot separated path to the field, starting from the root patch
+  pub path: String,
+
+  /// The serialized action from the patch being merged into
+  pub ours: serde_json::Value,
+
+  /// The serialized action from the patch being merged in
+  pub theirs: serde_json::Value,
+}
+
+impl Conflict {
+  pub fn new(
+    path: String,
+    ours: &PatchAction,
+    theirs: &PatchAction,
+  ) -> Result<Conflict, ProteanError> {
+    Ok(Conflict {
+      path,
+      ours: serde_json::to_value(ours)?,
+      theirs: serde_json::to_value(theirs)?,
+    })
+  }
+}
+
+impl<'a> Patch<'a> {
+  /// Combine another patch against the same model into this one
+  ///
+  /// Fields changed by only one of the patches are copied over, and nested patches on the same field
+  /// are merged recursively. If both patches change a field in different ways nothing is merged, and
+  /// every conflicting path is returned in a MergeConflict error.
+  pub fn merge(&mut self, patch: Patch<'a>) -> Result<(), ProteanError> {
+    let conflicts = self.conflicts(&patch)?;
+    if !conflicts.is_empty() {
+      return Err(ProteanError::MergeConflict(conflicts));
+    }
+
+    for theirs in patch.into_actions() {
+      let merged = match self.remove(&theirs.get_name()) {
+        None => theirs,
+        Some(ours) => merge_action(ours, theirs)?,
+      };
+      self.add_action(merged)?;
+    }
+    Ok(())
+  }
+
+  /// Merge the changes made to both ours and theirs since they were copied from base
+  ///
+  /// Fields changed on only one side are taken from that side and nested patches are merged field by
+  /// field. When both sides change the same value in any other way, the objects and lists they end up
+  /// with are merged key by key and element by element, and only the keys and elements that differ
+  /// from the base are sent. Anything changed differently on both sides is listed in the conflicts,
+  /// and the merged patch keeps our version of it.
+  pub fn merge3<T: Patchwork<'a>>(
+    base: &'a T,
+    ours: &'a T,
+    theirs: &'a T,
+  ) -> Result<(Patch<'a>, Vec<Conflict>), ProteanError> {
+    let mut conflicts = Vec::new();
+    let patch = base.diff(ours)?.merge_from_base(
+      base.diff(theirs)?,
+      &serde_json::to_value(base)?,
+      "",
+      &mut conflicts,
+    )?;
+    conflicts.sort_by(|left, right| left.path.cmp(&right.path));
+    Ok((patch, conflicts))
+  }
+
+  fn merge_from_base(
+    mut self,
+    theirs: Patch<'a>,
+    base: &Value,
+    prefix: &str,
+    conflicts: &mut Vec<Conflict>,
+  ) -> Result<Patch<'a>, ProteanError> {
+    for theirs in theirs.into_actions() {
+      let name = theirs.get_name();
+      let ours = match self.remove(&name) {
+        Some(ours) => ours,
+        None => {
+          self.add_action(theirs)?;
+          continue;
+        }
+      };
+
+      let path = join_path(prefix, &name);
+      let field = json::child(base, &name).unwrap_or(&Value::Null);
+      let merged = match (ours.get_value(), theirs.get_value()) {
+        _ if ours == theirs => ours,
+        // Only patches on the same target can be merged field by field, an update to a list element
+        // or map key is merged by value below
+        (PatchValue::Patch(_), PatchValue::Patch(_))
+          if ours.get_action() == theirs.get_action() =>
+        {
+          let action = ours.get_action().clone();
+          let expected = ours.get_expected();
+          let nested = ours.into_value().into_patch()?.merge_from_base(
+            theirs.into_value().into_patch()?,
+            field,
+            &path,
+            conflicts,
+          )?;
+          PatchAction::from_value(action, PatchValue::Patch(nested), expected)
+        }
+        (PatchValue::Patch(_), _) | (_, PatchValue::Patch(_))
+          if ours.get_action() == theirs.get_action() =>
+        {
+          conflicts.push(Conflict::new(path, &ours, &theirs)?);
+          ours
+        }
+        // Anything else is merged by the values each side ends up with, so edits made to a list or
+        // map by different actions can still be combined
+        _ => match (resolve(field, &ours), resolve(field, &theirs)) {
+          (Some(left), Some(right)) => {
+            let value = merge_json(&path, Some(field), Some(&left), Some(&right), conflicts)?
+              .unwrap_or(Value::Null);
+            let is_set = |act: &PatchAction| match act.get_value() {
+              PatchValue::Steps(_, steps) => steps
+                .iter()
+                .any(|step| matches!(step.get_action(), Action::Members(_))),
+              _ => matches!(act.get_action(), Action::Members(_)),
+            };
+            let changes = match is_set(&ours) || is_set(&theirs) {
+              true => diff_members(&name, field, &value)?,
+              false => json::diff(&name, field, &value)?,
+            };
+            match changes {
+              Some(changes) => with_expected(changes, ours.get_expected()),
+              None => continue,
+            }
+          }
+          _ => {
+            conflicts.push(Conflict::new(path, &ours, &theirs)?);
+            ours
+          }
+        },
+      };
+      self.add_action(merged)?;
+    }
+    Ok(self)
+  }
+
+  /// List every field that both patches change differently, without merging them
+  pub fn conflicts(&self, other: &Patch) -> Result<Vec<Conflict>, ProteanError> {
+    let mut conflicts = self.find_conflicts(other, "")?;
+    conflicts.sort_by(|left, right| left.path.cmp(&right.path));
+    Ok(conflicts)
+  }
+
+  fn find_conflicts(&self, other: &Patch, prefix: &str) -> Result<Vec<Conflict>, ProteanError> {
+    let mut conflicts = Vec::new();
+    for theirs in other.get_actions() {
+      let name = theirs.get_name();
+      let ours = match self.get(&name) {
+        Some(ours) => ours,
+        None => continue,
+      };
+
+      let path = join_path(prefix, &name);
+      match (ours.get_value(), theirs.get_value()) {
+        (PatchValue::Patch(left), PatchValue::Patch(right))
+          if ours.get_action() == theirs.get_action()
+            && ours.get_expected() == theirs.get_expected() =>
+        {
+          conflicts.extend(left.find_conflicts(right, &path)?)
+        }
+        _ if ours == theirs => (),
+        _ => conflicts.push(Conflict::new(path, ours, theirs)?),
+      }
+    }
+    Ok(conflicts)
+  }
+}
+
+/// Combine two actions on the same field that have already been checked for conflicts
+fn merge_action<'a>(
+  ours: PatchAction<'a>,
+  theirs: PatchAction<'a>,
+) -> Result<PatchAction<'a>, ProteanError> {
+  match (ours.get_value(), theirs.get_value()) {
+    (PatchValue::Patch(_), PatchValue::Patch(_)) => {
+      let action = ours.get_action().clone();
+      let expected = ours.get_expected();
+      let mut patch = ours.into_value().into_patch()?;
+      patch.merge(theirs.into_value().into_patch()?)?;
+      Ok(PatchAction::from_value(
+        action,
+        PatchValue::Patch(patch),
+        expected,
+      ))
+    }
+    // Identical actions, so either one will do
+    _ => Ok(ours),
+  }
+}
+
+fn join_path(prefix: &str, name: &str) -> String {
+  match prefix.is_empty() {
+    true => name.to_string(),
+    false => format!("{}.{}", prefix, name),
+  }
+}
+
+/// Check the first step of an action against the expected value of the one it replaces
+fn with_expected(action: PatchAction, expected: Option<u64>) -> PatchAction {
+  let name = action.get_name();
+  let mut steps = action.into_steps();
+  let first = steps.remove(0);
+  let act = first.get_action().clone();
+  steps.insert(
+    0,
+    PatchAction::from_value(act, first.into_value(), expected),
+  );
+  PatchAction::from_steps(name, steps)
+}
+
+/// The members to remove from the base set and then add to it to get the merged one
+fn diff_members<'a>(
+  name: &str,
+  base: &Value,
+  merged: &Value,
+) -> Result<Option<PatchAction<'a>>, ProteanError> {
+  let (base, merged) = match (base, merged) {
+    (Value::Array(base), Value::Array(merged)) => (base, merged),
+    _ => return json::diff(name, base, merged),
+  };
+  let missing = |from: &[Value], to: &[Value]| -> Vec<Value> {
+    let mut values: Vec<Value> = from.iter().filter(|v| !to.contains(v)).cloned().collect();
+    values.sort_by_cached_key(|value| value.to_string());
+    values
+  };
+
+  let mut steps = Vec::new();
+  for (act, values) in [
+    (SetAction::Remove, missing(base, merged)),
+    (SetAction::Add, missing(merged, base)),
+  ] {
+    for value in values {
+      steps.push(PatchAction::new(
+        Action::Members(act.clone()),
+        OwnedValue::new(name.to_string(), value),
+        None,
+      ));
+    }
+  }
+  Ok(match steps.is_empty() {
+    true => None,
+    false => Some(PatchAction::from_steps(name.to_string(), steps)),
+  })
+}
+
+/// The value a field will have after an action, or None if the action can't be applied to it
+fn resolve(field: &Value, action: &PatchAction) -> Option<Value> {
+  let mut value = field.clone();
+  json::apply_action(&mut value, action.try_clone().ok()?).ok()?;
+  Some(value)
+}
+
+/// Three way merge of a JSON value, where None is a value that does not exist on that side
+fn merge_json(
+  path: &str,
+  base: Option<&Value>,
+  ours: Option<&Value>,
+  theirs: Option<&Value>,
+  conflicts: &mut Vec<Conflict>,
+) -> Result<Option<Value>, ProteanError> {
+  if ours == theirs || theirs == base {
+    return Ok(ours.cloned());
+  }
+  if ours == base {
+    return Ok(theirs.cloned());
+  }
+
+  Ok(Some(match (base, ours, theirs) {
+    (Some(Value::Object(base)), Some(Value::Object(ours)), Some(Value::Object(theirs))) => {
+      let mut merged = serde_json::Map::new();
+      let keys: std::collections::BTreeSet<&String> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect();
+      for key in keys {
+        let value = merge_json(
+          &join_path(path, key),
+          base.get(key),
+          ours.get(key),
+          theirs.get(key),
+          conflicts,
+        )?;
+        if let Some(value) = value {
+          merged.insert(key.clone(), value);
+        }
+      }
+      Value::Object(merged)
+    }
+    (Some(Value::Array(base)), Some(Value::Array(ours)), Some(Value::Array(theirs))) => {
+      Value::Array(merge_list(path, base, ours, theirs, conflicts)?)
+    }
+    _ => {
+      conflicts.push(Conflict::new(
+        path.to_string(),
+        &conflict_side(path, ours),
+        &conflict_side(path, theirs),
+      )?);
+      return Ok(ours.cloned());
+    }
+  }))
+}
+
+/// Merge lists element by element when no side changed the length, otherwise take the changed range
+/// from each side as long as they don't overlap
+fn merge_list(
+  path: &str,
+  base: &[Value],
+  ours: &[Value],
+  theirs: &[Value],
+  conflicts: &mut Vec<Conflict>,
+) -> Result<Vec<Value>, ProteanError> {
+  if base.len() == ours.len() && base.len() == theirs.len() {
+    let mut merged = Vec::with_capacity(base.len());
+    for (idx, value) in base.iter().enumerate() {
+      let value = merge_json(
+        &join_path(path, &idx.to_string()),
+        Some(value),
+        ours.get(idx),
+        theirs.get(idx),
+        conflicts,
+      )?;
+      merged.extend(value);
+    }
+    return Ok(merged);
+  }
+
+  let (first, second) = match (ListEdit::new(base, ours), ListEdit::new(base, theirs)) {
+    (left, right) if left.start <= right.start => (left, right),
+    (left, right) => (right, left),
+  };
+  if first.start == second.start || first.end > second.start {
+    let side = |list: &[Value]| conflict_side(path, Some(&Value::Array(list.to_vec())));
+    conflicts.push(Conflict::new(path.to_string(), &side(ours), &side(theirs))?);
+    return Ok(ours.to_vec());
+  }
+
+  let mut merged = base[..first.start].to_vec();
+  merged.extend_from_slice(first.values);
+  merged.extend_from_slice(&base[first.end..second.start]);
+  merged.extend_from_slice(second.values);
+  merged.extend_from_slice(&base[second.end..]);
+  Ok(merged)
+}
+
+/// The range of the base list that was replaced, and the values that replaced it
+struct ListEdit<'v> {
+  start: usize,
+  end: usize,
+  values: &'v [Value],
+}
+
+impl<'v> ListEdit<'v> {
+  fn new(base: &[Value], changed: &'v [Value]) -> ListEdit<'v> {
+    let start = base
+      .iter()
+      .zip(changed)
+      .take_while(|(left, right)| left == right)
+      .count();
+    let end = base[start..]
+      .iter()
+      .rev()
+      .zip(changed[start..].iter().rev())
+      .take_while(|(left, right)| left == right)
+      .count();
+    ListEdit {
+      start,
+      end: base.len() - end,
+      values: &changed[start..changed.len() - end],
+    }
+  }
+}
+
+/// The action one side of a conflict made to a value, with a missing value being deleted
+fn conflict_side(path: &str, value: Option<&Value>) -> PatchAction<'static> {
+  let name = path.rsplit('.').next().unwrap_or(path).to_string();
+  match value {
+    Some(value) => PatchAction::new(
+      Action::Set,
+      OwnedValue::new(name.clone(), value.clone()),
+      None,
+    ),
+    None => PatchAction::new(
+      Action::Map(MapAction::Delete(name.clone())),
+      OwnedValue::new(name, Value::Null),
+      None,
+    ),
+  }
+}
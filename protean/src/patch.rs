@@ -1,12 +1,15 @@
 //! A transferable set of transformations to update one structure to match another
 
-use crate::local::*;
+use crate::{hash, impls::json, local::*};
 
-use serde::ser::{Error as SerdeError, SerializeMap, SerializeTuple};
+use serde::{
+  de::{self, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor},
+  ser::{Error as SerdeError, SerializeMap, SerializeTuple},
+};
 
 /// A recursive patch designed to be applied to a given object
 /// This is the root,
-#[derive(Default, Debug, Serialize)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct Patch<'a> {
   /// A name that the patch is referenced by (usually the field name taken from Patchworthy)
   name: String,
@@ -21,12 +24,18 @@ pub struct Patch<'a> {
   actions: PatchActions<'a>,
 }
 
+/// A patch that owns all of its values
+///
+/// Unlike a patch created directly from diff or as_patch, this does not borrow from the object it
+/// was made from. It can outlive that object, be stored in a history, or be sent to another thread.
+pub type PatchBuf = Patch<'static>;
+
 impl<'a> Patch<'a> {
   pub fn new(name: String) -> Patch<'a> {
     Patch {
       name,
       version: None,
-      options: Some(PatchOptions::default()),
+      options: None,
       actions: PatchActions::new(),
     }
   }
@@ -35,9 +44,62 @@ impl<'a> Patch<'a> {
     self.name.clone()
   }
 
-  /// Attempt to combine two patches, if there is no conflict.
-  pub fn merge(&mut self, _patch: Patch) -> Result<(), ProteanError> {
-    todo!("Work on merge")
+  /// Check if the patch has any actions to perform
+  pub fn is_empty(&self) -> bool {
+    self.actions.0.is_empty()
+  }
+
+  /// Iterate over the actions for each field in the patch
+  pub fn get_actions(&self) -> impl Iterator<Item = &PatchAction<'a>> {
+    self.actions.0.values()
+  }
+
+  /// Get the action for the given field, if there is one
+  pub fn get(&self, name: &str) -> Option<&PatchAction<'a>> {
+    self.actions.0.get(name)
+  }
+
+  /// Take the action for the given field out of the patch
+  pub fn remove(&mut self, name: &str) -> Option<PatchAction<'a>> {
+    self.actions.0.remove(name)
+  }
+
+  /// The settings for applying this patch, if any were configured
+  pub fn get_options(&self) -> Option<&PatchOptions> {
+    self.options.as_ref()
+  }
+
+  pub fn set_options(&mut self, options: PatchOptions) {
+    self.options = Some(options);
+  }
+
+  /// Consume the patch, returning the action for each field
+  pub fn into_actions(self) -> impl Iterator<Item = PatchAction<'a>> {
+    self.actions.0.into_values()
+  }
+
+  /// Create the patch that will undo this one
+  ///
+  /// The base is the object this patch is about to be applied to, which holds the values that will
+  /// be overwritten or removed. Applying this patch and then the inverse leaves the base unchanged.
+  /// The patch is applied to a copy of the base, so fields are found the same way `apply` finds them.
+  /// Plain JSON can be inverted with `impls::json::invert_patch` instead.
+  pub fn invert<'b, T: Patchwork<'b>>(&self, base: &T) -> Result<PatchBuf, ProteanError> {
+    base.clone().apply(self.try_clone()?)?.into_owned()
+  }
+
+  /// Detach the patch from the objects it borrows, copying each value it holds
+  pub fn into_owned(self) -> Result<PatchBuf, ProteanError> {
+    let mut owned = Patch {
+      name: self.name,
+      version: self.version,
+      options: self.options,
+      actions: PatchActions::new(),
+    };
+    for action in self.actions.0.into_values() {
+      owned.actions.add(action.into_owned()?)?;
+    }
+    Ok(owned)
   }
 
   /// Adds a patch to a child field
@@ -53,23 +115,112 @@ impl<'a> Patch<'a> {
     let act = PatchAction::new(action, field, expected);
     self.actions.add(act)
   }
+
+  /// Adds an already built action, such as one returned from diffing a field
+  pub fn add_action(&mut self, action: PatchAction<'a>) -> Result<(), ProteanError> {
+    self.actions.add(action)
+  }
+
+  /// Add an action to run after any the field already has, making them a sequence of steps
+  pub fn add_step(&mut self, action: PatchAction<'a>) -> Result<(), ProteanError> {
+    let name = action.get_name();
+    match self.remove(&name) {
+      None => self.add_action(action),
+      Some(prev) => {
+        let mut steps = prev.into_steps();
+        steps.extend(action.into_steps());
+        self.add_action(PatchAction::from_steps(name, steps))
+      }
+    }
+  }
+
+  /// Copy the patch, detaching it from anything it borrows
+  pub fn try_clone(&self) -> Result<PatchBuf, ProteanError> {
+    let mut copy = Patch {
+      name: self.name.clone(),
+      version: self.version.clone(),
+      options: self.options.clone(),
+      actions: PatchActions::new(),
+    };
+    for action in self.get_actions() {
+      copy.actions.add(action.try_clone()?)?;
+    }
+    Ok(copy)
+  }
 }
 
-// impl<'a> Deserialize<'a> for Patch<'a> {}
+impl<'a, 'b> PartialEq<Patch<'b>> for Patch<'a> {
+  fn eq(&self, other: &Patch<'b>) -> bool {
+    self.name == other.name
+      && self.version == other.version
+      && self.options == other.options
+      && self.actions == other.actions
+  }
+}
 
 /// Specific settings that modify how a patch is applied
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PatchOptions {
   /// Default is true. Inserts will automatically be tried as upserts.
   allow_upsert: bool,
+
+  /// What to do with an action whose field doesn't match its expected hash
+  on_mismatch: OnMismatch,
+}
+
+impl Default for PatchOptions {
+  fn default() -> PatchOptions {
+    PatchOptions {
+      allow_upsert: true,
+      on_mismatch: OnMismatch::default(),
+    }
+  }
+}
+
+impl PatchOptions {
+  pub fn allow_upsert(&self) -> bool {
+    self.allow_upsert
+  }
+
+  pub fn set_allow_upsert(&mut self, allow_upsert: bool) {
+    self.allow_upsert = allow_upsert;
+  }
+
+  pub fn on_mismatch(&self) -> OnMismatch {
+    self.on_mismatch
+  }
+
+  pub fn set_on_mismatch(&mut self, on_mismatch: OnMismatch) {
+    self.on_mismatch = on_mismatch;
+  }
+}
+
+/// How to handle an action when the field it changes no longer hashes to the expected value
+///
+/// This is what makes optimistic concurrency possible: a patch made against an older copy of the
+/// object will notice that the field was changed in the meantime.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum OnMismatch {
+  /// Roll back the whole patch and return an ExpectedMismatch error
+  #[default]
+  Fail,
+
+  /// Leave the field alone and carry on with the rest of the patch
+  Skip,
+
+  /// Apply the action anyway
+  Force,
 }
 
+/// The action for each field, kept in order of the field names so they are always applied (and any
+/// errors found) in the same order
 #[derive(Default, Debug)]
-pub struct PatchActions<'a>(HashMap<String, PatchAction<'a>>);
+pub struct PatchActions<'a>(BTreeMap<String, PatchAction<'a>>);
 
 impl<'a> PatchActions<'a> {
   pub fn new() -> PatchActions<'a> {
-    PatchActions(HashMap::new())
+    PatchActions(BTreeMap::new())
   }
 
   fn add(&mut self, action: PatchAction<'a>) -> Result<(), ProteanError> {
@@ -91,21 +242,61 @@ impl<'a> Serialize for PatchActions<'a> {
   where
     S: serde::Serializer,
   {
-    let mut state = serializer.serialize_map(Some(self.0.len()))?;
-    for (k, v) in &self.0 {
-      match &v.action {
-        Action::Null => continue,
-        Action::List(_act) => todo!("No list actions yet"),
-        Action::Map(_act) => todo!("No map actions yet"),
-        Action::Reset => todo!("No Default action yet"),
-        Action::Clear => todo!("No Clear action yet"),
-        Action::Set => state.serialize_entry(k, v)?,
-      };
+    // Null actions have nothing to do, so they are left out
+    let actions: Vec<_> = self
+      .0
+      .iter()
+      .filter(|(_, v)| v.action != Action::Null)
+      .collect();
+    let mut state = serializer.serialize_map(Some(actions.len()))?;
+    for (k, v) in actions {
+      state.serialize_entry(k, v)?;
     }
     state.end()
   }
 }
 
+impl<'a, 'b> PartialEq<PatchActions<'b>> for PatchActions<'a> {
+  fn eq(&self, other: &PatchActions<'b>) -> bool {
+    self.0.len() == other.0.len()
+      && self
+        .0
+        .iter()
+        .all(|(k, v)| other.0.get(k).is_some_and(|o| v == o))
+  }
+}
+
+impl<'de, 'a> Deserialize<'de> for PatchActions<'a> {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: serde::Deserializer<'de>,
+  {
+    struct ActionsVisitor<'a>(std::marker::PhantomData<PatchActions<'a>>);
+
+    impl<'de, 'a> Visitor<'de> for ActionsVisitor<'a> {
+      type Value = PatchActions<'a>;
+
+      fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a map of field names to patch actions")
+      }
+
+      fn visit_map<M>(self, mut map: M) -> Result<Self::Value, M::Error>
+      where
+        M: MapAccess<'de>,
+      {
+        let mut actions = PatchActions::new();
+        while let Some(name) = map.next_key::<String>()? {
+          let action = map.next_value_seed(NamedSeed::<PatchAction>::new(name))?;
+          actions.add(action).map_err(de::Error::custom)?;
+        }
+        Ok(actions)
+      }
+    }
+
+    deserializer.deserialize_map(ActionsVisitor(std::marker::PhantomData))
+  }
+}
+
 #[derive(Debug)]
 pub struct PatchAction<'a> {
   action: Action,
@@ -113,10 +304,13 @@ pub struct PatchAction<'a> {
   /// The value to use when performing an action
   value: PatchValue<'a>,
 
-  /// A hash of the original PatchValue
+  /// A hash of the original PatchValue, calculated with `hash::content_hash`
   ///
   /// An optional state check to make sure the patch is being applied to a specific value
   expected: Option<u64>,
+
+  /// Options inherited from the patch while it is being applied. These are never serialized.
+  options: Option<PatchOptions>,
 }
 
 impl<'a> PatchAction<'a> {
@@ -129,12 +323,159 @@ impl<'a> PatchAction<'a> {
       action,
       value: PatchValue::Value(Box::new(value)),
       expected,
+      options: None,
+    }
+  }
+
+  /// Create an action from an already wrapped value
+  pub fn from_value(
+    action: Action,
+    value: PatchValue<'a>,
+    expected: Option<u64>,
+  ) -> PatchAction<'a> {
+    PatchAction {
+      action,
+      value,
+      expected,
+      options: None,
     }
   }
 
+  /// Combine several actions on the same field into one that applies them in order
+  ///
+  /// Steps that are themselves a sequence are flattened into this one, and Null steps are dropped. A
+  /// single step is returned as it is.
+  pub fn from_steps(name: String, steps: Vec<PatchAction<'a>>) -> PatchAction<'a> {
+    let mut steps: Vec<PatchAction<'a>> = steps
+      .into_iter()
+      .flat_map(PatchAction::into_steps)
+      .filter(|step| step.action != Action::Null)
+      .collect();
+    match steps.len() {
+      0 => PatchAction::new(
+        Action::Null,
+        OwnedValue::new(name, serde_json::Value::Null),
+        None,
+      ),
+      1 => steps.remove(0),
+      _ => PatchAction::from_value(Action::Steps, PatchValue::Steps(name, steps), None),
+    }
+  }
+
+  /// Split the action into the steps it performs, which is just itself unless it is a sequence
+  pub fn into_steps(self) -> Vec<PatchAction<'a>> {
+    match self.value {
+      PatchValue::Steps(_, steps) => steps,
+      value => vec![PatchAction { value, ..self }],
+    }
+  }
+
+  /// Wrap a patch for a nested Patchwork so it can be added as a single field
+  ///
+  /// The patch is renamed to the field so it can be found again when applied
+  pub fn from_patch(name: String, mut patch: Patch<'a>) -> PatchAction<'a> {
+    patch.name = name;
+    PatchAction::from_value(Action::Set, PatchValue::Patch(patch), None)
+  }
+
   pub fn get_name(&self) -> String {
     self.value.get_field_name()
   }
+
+  pub fn get_action(&self) -> &Action {
+    &self.action
+  }
+
+  pub fn get_value(&self) -> &PatchValue<'a> {
+    &self.value
+  }
+
+  /// The options of the patch this action is being applied from, or the defaults
+  pub fn get_options(&self) -> PatchOptions {
+    self.options.clone().unwrap_or_default()
+  }
+
+  /// Pass the options of the patch being applied down to this action
+  ///
+  /// A nested patch that has no options of its own inherits them as well.
+  pub fn inherit_options(&mut self, options: &PatchOptions) {
+    self.options = Some(options.clone());
+    if let PatchValue::Patch(patch) = &mut self.value {
+      if patch.options.is_none() {
+        patch.options = Some(options.clone());
+      }
+    }
+  }
+
+  /// The hash the field should have before this action is applied
+  ///
+  /// A sequence of steps is checked against the expected value of its first step.
+  pub fn get_expected(&self) -> Option<u64> {
+    match &self.value {
+      PatchValue::Steps(_, steps) => steps.first().and_then(PatchAction::get_expected),
+      _ => self.expected,
+    }
+  }
+
+  /// Check the current value of the field against the expected hash
+  ///
+  /// Returns whether the action should be applied, which is only false when skipping mismatches.
+  pub fn check_expected(
+    &self,
+    current: &serde_json::Value,
+    on_mismatch: OnMismatch,
+  ) -> Result<bool, ProteanError> {
+    let expected = match self.get_expected() {
+      Some(expected) => expected,
+      None => return Ok(true),
+    };
+    let found = hash::content_hash(current);
+    match (found == expected, on_mismatch) {
+      (true, _) | (false, OnMismatch::Force) => Ok(true),
+      (false, OnMismatch::Skip) => Ok(false),
+      (false, OnMismatch::Fail) => Err(ProteanError::ExpectedMismatch {
+        path: self.get_name(),
+        expected,
+        found,
+      }),
+    }
+  }
+
+  pub fn into_value(self) -> PatchValue<'a> {
+    self.value
+  }
+
+  /// Detach the action from the object its value borrows from
+  pub fn into_owned(self) -> Result<PatchAction<'static>, ProteanError> {
+    Ok(PatchAction::from_value(
+      self.action,
+      self.value.into_owned()?,
+      self.expected,
+    ))
+  }
+
+  /// Copy the action, detaching it from anything it borrows
+  pub fn try_clone(&self) -> Result<PatchAction<'static>, ProteanError> {
+    let value = match &self.value {
+      PatchValue::Value(val) => PatchValue::Value(Box::new(OwnedValue::new(
+        val.get_field_name(),
+        val.as_json()?,
+      ))),
+      PatchValue::Patch(patch) => PatchValue::Patch(patch.try_clone()?),
+      PatchValue::Steps(name, steps) => PatchValue::Steps(
+        name.clone(),
+        steps
+          .iter()
+          .map(PatchAction::try_clone)
+          .collect::<Result<_, _>>()?,
+      ),
+    };
+    Ok(PatchAction::from_value(
+      self.action.clone(),
+      value,
+      self.expected,
+    ))
+  }
 }
 
 // Manually create a patch serializer since derive doesn't work easily
@@ -143,33 +484,318 @@ impl<'a> Serialize for PatchAction<'a> {
   where
     S: serde::Serializer,
   {
-    let size = match self.expected.is_some() {
-      true => 2,
-      false => 3,
+    // A sequence is written as a list of the actions in it
+    if let PatchValue::Steps(_, steps) = &self.value {
+      return serializer.collect_seq(steps);
+    }
+
+    // Reset and Clear have no value, which is only written as null to leave room for expected
+    let has_value = !matches!(self.action, Action::Reset | Action::Clear);
+    let size = match (has_value, self.expected.is_some()) {
+      (_, true) => 3,
+      (true, false) => 2,
+      (false, false) => 1,
     };
     let mut state = serializer.serialize_tuple(size)?;
     match &self.action {
-      Action::Null => {
-        unreachable!("Should never be trying to directly serialize a Null patch action")
+      Action::Null | Action::Steps => {
+        return Err(S::Error::custom(format!(
+          "{:?} patch actions have nothing to serialize",
+          self.action
+        )))
+      }
+      Action::List(act) => {
+        let value = || self.value.as_json().map_err(S::Error::custom);
+        match act {
+          ListAction::Swap(left, right) => {
+            state.serialize_element("List.Swap")?;
+            state.serialize_element(&(left, right))?;
+          }
+          ListAction::Remove(idx) => {
+            state.serialize_element("List.Remove")?;
+            state.serialize_element(idx)?;
+          }
+          ListAction::Insert(idx) => {
+            state.serialize_element("List.Insert")?;
+            state.serialize_element(&(idx, value()?))?;
+          }
+          ListAction::Append() => {
+            state.serialize_element("List.Append")?;
+            state.serialize_element(&value()?)?;
+          }
+          ListAction::Move(from, to) => {
+            state.serialize_element("List.Move")?;
+            state.serialize_element(&(from, to))?;
+          }
+          ListAction::Update(idx) => {
+            state.serialize_element("List.Update")?;
+            state.serialize_element(&(idx, &self.value))?;
+          }
+        }
       }
-      Action::List(_act) => todo!("No list actions yet"),
-      Action::Map(_act) => todo!("No map actions yet"),
-      Action::Reset => todo!("No Default action yet"),
-      Action::Clear => todo!("No Clear action yet"),
+      Action::Map(act) => match act {
+        MapAction::Insert(key) => {
+          let value = self.value.as_json().map_err(S::Error::custom)?;
+          state.serialize_element("Map.Insert")?;
+          state.serialize_element(&(key, value))?;
+        }
+        MapAction::Update(key) => {
+          state.serialize_element("Map.Update")?;
+          state.serialize_element(&(key, &self.value))?;
+        }
+        MapAction::Delete(key) => {
+          state.serialize_element("Map.Delete")?;
+          state.serialize_element(key)?;
+        }
+      },
+      Action::Members(act) => {
+        let value = self.value.as_json().map_err(S::Error::custom)?;
+        match act {
+          SetAction::Add => state.serialize_element("Set.Add")?,
+          SetAction::Remove => state.serialize_element("Set.Remove")?,
+        }
+        state.serialize_element(&value)?;
+      }
+      Action::Reset => state.serialize_element("Reset")?,
+      Action::Clear => state.serialize_element("Clear")?,
       Action::Set => {
         state.serialize_element("Set")?;
         state.serialize_element(&self.value)?;
       }
     };
+    if !has_value && self.expected.is_some() {
+      state.serialize_element(&())?;
+    }
 
+    if let Some(expected) = self.expected {
+      state.serialize_element(&expected)?;
+    }
     state.end()
   }
 }
 
+impl<'a, 'b> PartialEq<PatchAction<'b>> for PatchAction<'a> {
+  fn eq(&self, other: &PatchAction<'b>) -> bool {
+    self.action == other.action && self.expected == other.expected && self.value == other.value
+  }
+}
+
+/// Every tag that can start a serialized PatchAction
+const ACTION_TAGS: &[&str] = &[
+  "Set",
+  "Reset",
+  "Clear",
+  "List.Swap",
+  "List.Remove",
+  "List.Insert",
+  "List.Append",
+  "List.Move",
+  "List.Update",
+  "Map.Insert",
+  "Map.Update",
+  "Map.Delete",
+  "Set.Add",
+  "Set.Remove",
+];
+
+/// Deserializes a value that needs to know the name of the field it belongs to
+///
+/// Field names are only written once as the key in PatchActions, so they need to be passed down to
+/// the action and value being deserialized.
+struct NamedSeed<T> {
+  name: String,
+  _type: std::marker::PhantomData<T>,
+}
+
+impl<T> NamedSeed<T> {
+  fn new(name: String) -> NamedSeed<T> {
+    NamedSeed {
+      name,
+      _type: std::marker::PhantomData,
+    }
+  }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for NamedSeed<PatchAction<'a>> {
+  type Value = PatchAction<'a>;
+
+  fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+  where
+    D: serde::Deserializer<'de>,
+  {
+    deserializer.deserialize_seq(self)
+  }
+}
+
+impl<'de, 'a> Visitor<'de> for NamedSeed<PatchAction<'a>> {
+  type Value = PatchAction<'a>;
+
+  fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(
+      f,
+      "a patch action of the form [action, value, expected], or a list of them"
+    )
+  }
+
+  fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let first = seq
+      .next_element_seed(NamedSeed::<FirstElement>::new(self.name.clone()))?
+      .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+    let tag = match first {
+      FirstElement::Tag(tag) => tag,
+      FirstElement::Step(step) => {
+        let mut steps = vec![step];
+        while let Some(step) =
+          seq.next_element_seed(NamedSeed::<PatchAction>::new(self.name.clone()))?
+        {
+          steps.push(step);
+        }
+        return Ok(PatchAction::from_steps(self.name, steps));
+      }
+    };
+
+    let (action, value) = match tag.as_str() {
+      "Set" => {
+        let value = seq
+          .next_element_seed(NamedSeed::<PatchValue>::new(self.name.clone()))?
+          .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        (Action::Set, value)
+      }
+      "Reset" | "Clear" => {
+        let action = match tag.as_str() {
+          "Reset" => Action::Reset,
+          _ => Action::Clear,
+        };
+        seq.next_element::<()>()?;
+        let value = OwnedValue::new(self.name.clone(), serde_json::Value::Null);
+        (action, PatchValue::Value(Box::new(value)))
+      }
+      list if list.starts_with("List.") => {
+        let missing = || de::Error::invalid_length(1, &self);
+        let owned = |value| PatchValue::Value(Box::new(OwnedValue::new(self.name.clone(), value)));
+        let (act, value) = match &list["List.".len()..] {
+          "Swap" => {
+            let (left, right) = seq.next_element()?.ok_or_else(missing)?;
+            (
+              ListAction::Swap(left, right),
+              owned(serde_json::Value::Null),
+            )
+          }
+          "Remove" => {
+            let idx = seq.next_element()?.ok_or_else(missing)?;
+            (ListAction::Remove(idx), owned(serde_json::Value::Null))
+          }
+          "Insert" => {
+            let (idx, value) = seq.next_element()?.ok_or_else(missing)?;
+            (ListAction::Insert(idx), owned(value))
+          }
+          "Append" => (
+            ListAction::Append(),
+            owned(seq.next_element()?.ok_or_else(missing)?),
+          ),
+          "Move" => {
+            let (from, to) = seq.next_element()?.ok_or_else(missing)?;
+            (ListAction::Move(from, to), owned(serde_json::Value::Null))
+          }
+          "Update" => {
+            let (idx, value) = seq
+              .next_element_seed(NamedSeed::<(usize, PatchValue)>::new(self.name.clone()))?
+              .ok_or_else(missing)?;
+            (ListAction::Update(idx), value)
+          }
+          _ => return Err(de::Error::unknown_variant(&tag, ACTION_TAGS)),
+        };
+        (Action::List(act), value)
+      }
+      map if map.starts_with("Map.") => {
+        let missing = || de::Error::invalid_length(1, &self);
+        let owned = |value| PatchValue::Value(Box::new(OwnedValue::new(self.name.clone(), value)));
+        let (act, value) = match &map["Map.".len()..] {
+          "Insert" => {
+            let (key, value) = seq.next_element()?.ok_or_else(missing)?;
+            (MapAction::Insert(key), owned(value))
+          }
+          "Update" => {
+            let (key, value) = seq
+              .next_element_seed(NamedSeed::<(String, PatchValue)>::new(self.name.clone()))?
+              .ok_or_else(missing)?;
+            (MapAction::Update(key), value)
+          }
+          "Delete" => {
+            let key = seq.next_element()?.ok_or_else(missing)?;
+            (MapAction::Delete(key), owned(serde_json::Value::Null))
+          }
+          _ => return Err(de::Error::unknown_variant(&tag, ACTION_TAGS)),
+        };
+        (Action::Map(act), value)
+      }
+      "Set.Add" | "Set.Remove" => {
+        let act = match tag.as_str() {
+          "Set.Add" => SetAction::Add,
+          _ => SetAction::Remove,
+        };
+        let value = seq
+          .next_element()?
+          .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let value = OwnedValue::new(self.name.clone(), value);
+        (Action::Members(act), PatchValue::Value(Box::new(value)))
+      }
+      _ => return Err(de::Error::unknown_variant(&tag, ACTION_TAGS)),
+    };
+
+    let expected = seq.next_element::<Option<u64>>()?.flatten();
+    Ok(PatchAction::from_value(action, value, expected))
+  }
+}
+
+/// The start of a serialized PatchAction, which is either the tag of a single action or the first of
+/// a list of steps
+enum FirstElement<'a> {
+  Tag(String),
+  Step(PatchAction<'a>),
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for NamedSeed<FirstElement<'a>> {
+  type Value = FirstElement<'a>;
+
+  fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+  where
+    D: serde::Deserializer<'de>,
+  {
+    deserializer.deserialize_any(self)
+  }
+}
+
+impl<'de, 'a> Visitor<'de> for NamedSeed<FirstElement<'a>> {
+  type Value = FirstElement<'a>;
+
+  fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "an action tag or a patch action")
+  }
+
+  fn visit_str<E: de::Error>(self, tag: &str) -> Result<Self::Value, E> {
+    Ok(FirstElement::Tag(tag.to_string()))
+  }
+
+  fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let seed = NamedSeed::<PatchAction>::new(self.name);
+    Ok(FirstElement::Step(seed.visit_seq(seq)?))
+  }
+}
+
 #[derive(Debug)]
 pub enum PatchValue<'a> {
   Value(Box<dyn Patchworthy<'a> + 'a>),
   Patch(Patch<'a>),
+
+  /// The field name and each action of an `Action::Steps`
+  Steps(String, Vec<PatchAction<'a>>),
 }
 
 impl<'a> PatchValue<'a> {
@@ -177,6 +803,59 @@ impl<'a> PatchValue<'a> {
     match self {
       PatchValue::Value(val) => val.get_field_name(),
       PatchValue::Patch(patch) => patch.get_name(),
+      PatchValue::Steps(name, _) => name.clone(),
+    }
+  }
+
+  /// Get the JSON of a value, erroring if it holds a nested patch or steps instead
+  pub fn as_json(&self) -> Result<serde_json::Value, ProteanError> {
+    match self {
+      PatchValue::Value(val) => val.as_json(),
+      PatchValue::Patch(_) | PatchValue::Steps(_, _) => Err(ProteanError::InvalidPatchType),
+    }
+  }
+
+  /// Copy a borrowed value into an OwnedValue, recursing into nested patches
+  pub fn into_owned(self) -> Result<PatchValue<'static>, ProteanError> {
+    Ok(match self {
+      PatchValue::Value(val) => PatchValue::Value(Box::new(OwnedValue::new(
+        val.get_field_name(),
+        val.as_json()?,
+      ))),
+      PatchValue::Patch(patch) => PatchValue::Patch(patch.into_owned()?),
+      PatchValue::Steps(name, steps) => PatchValue::Steps(
+        name,
+        steps
+          .into_iter()
+          .map(PatchAction::into_owned)
+          .collect::<Result<_, _>>()?,
+      ),
+    })
+  }
+
+  /// Unwrap a nested patch, erroring if it holds a value instead
+  pub fn into_patch(self) -> Result<Patch<'a>, ProteanError> {
+    match self {
+      PatchValue::Patch(patch) => Ok(patch),
+      _ => Err(ProteanError::InvalidPatchType),
+    }
+  }
+}
+
+impl<'a, 'b> PartialEq<PatchValue<'b>> for PatchValue<'a> {
+  fn eq(&self, other: &PatchValue<'b>) -> bool {
+    match (self, other) {
+      (PatchValue::Value(left), PatchValue::Value(right)) => {
+        left.get_field_name() == right.get_field_name()
+          && matches!((left.as_json(), right.as_json()), (Ok(l), Ok(r)) if l == r)
+      }
+      (PatchValue::Patch(left), PatchValue::Patch(right)) => left == right,
+      (PatchValue::Steps(left_name, left), PatchValue::Steps(right_name, right)) => {
+        left_name == right_name
+          && left.len() == right.len()
+          && left.iter().zip(right).all(|(l, r)| l == r)
+      }
+      _ => false,
     }
   }
 }
@@ -194,14 +873,134 @@ impl<'a> Serialize for PatchValue<'a> {
       PatchValue::Patch(patch) => {
         serializer.serialize_newtype_variant("PatchValue", 1, "Patch", &patch)
       }
+      PatchValue::Steps(_, steps) => serializer.collect_seq(steps),
+    }
+  }
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for NamedSeed<PatchValue<'a>> {
+  type Value = PatchValue<'a>;
+
+  fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+  where
+    D: serde::Deserializer<'de>,
+  {
+    deserializer.deserialize_enum("PatchValue", &["Value", "Patch"], self)
+  }
+}
+
+impl<'de, 'a> Visitor<'de> for NamedSeed<PatchValue<'a>> {
+  type Value = PatchValue<'a>;
+
+  fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "a PatchValue of either a Value or a Patch")
+  }
+
+  fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+  where
+    A: EnumAccess<'de>,
+  {
+    let (variant, access) = data.variant::<String>()?;
+    match variant.as_str() {
+      "Value" => {
+        let value = access.newtype_variant::<serde_json::Value>()?;
+        Ok(PatchValue::Value(Box::new(OwnedValue::new(
+          self.name, value,
+        ))))
+      }
+      "Patch" => {
+        let mut patch = access.newtype_variant::<Patch>()?;
+        patch.name = self.name;
+        Ok(PatchValue::Patch(patch))
+      }
+      _ => Err(de::Error::unknown_variant(&variant, &["Value", "Patch"])),
     }
   }
 }
 
+/// A map key or list index paired with the value for it, as used by `Map.Update` and `List.Update`
+impl<'de, 'a, K: DeserializeOwned> DeserializeSeed<'de> for NamedSeed<(K, PatchValue<'a>)> {
+  type Value = (K, PatchValue<'a>);
+
+  fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+  where
+    D: serde::Deserializer<'de>,
+  {
+    deserializer.deserialize_tuple(2, self)
+  }
+}
+
+impl<'de, 'a, K: DeserializeOwned> Visitor<'de> for NamedSeed<(K, PatchValue<'a>)> {
+  type Value = (K, PatchValue<'a>);
+
+  fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "a key or index and the PatchValue for it")
+  }
+
+  fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+  where
+    A: SeqAccess<'de>,
+  {
+    let key = seq
+      .next_element::<K>()?
+      .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+    let value = seq
+      .next_element_seed(NamedSeed::<PatchValue>::new(self.name.clone()))?
+      .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+    Ok((key, value))
+  }
+}
+
+/// A field value that is no longer tied to the struct it came from
+///
+/// Values created while patching, like the previous value of a field that is about to be
+/// overwritten, have nothing left to borrow from so they keep a copy of the field as JSON.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedValue {
+  name: String,
+  value: serde_json::Value,
+}
+
+impl OwnedValue {
+  pub fn new(name: String, value: serde_json::Value) -> OwnedValue {
+    OwnedValue { name, value }
+  }
+}
+
+/// Diffed with `json::diff`, so an owned copy of a field still gives nested patches and list actions
+impl<'a> Patchworthy<'a> for OwnedValue {
+  fn get_field_name(&self) -> String {
+    self.name.clone()
+  }
+
+  fn as_json(&self) -> Result<serde_json::Value, ProteanError> {
+    Ok(self.value.clone())
+  }
+
+  fn diff(&self, other: Self) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    json::diff(&self.name, &self.value, &other.value)
+  }
+}
+
+/// Serializes as a single entry map, the same shape as a derived field enum
+impl Serialize for OwnedValue {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(&self.name, &self.value)?;
+    map.end()
+  }
+}
+
+impl Display for OwnedValue {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}: {}", self.name, self.value)
+  }
+}
+
 /// Actions that a patch can perform against a given target based upon its type.
 // TODO: I'm not sure the function option this is worthwhile, as a function cannot be sent via API
 // Func(Box<FnMut()>)
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Action {
   /// No action to be performed
   Null,
@@ -225,10 +1024,21 @@ pub enum Action {
 
   /// An unordered set of key/value pairs
   Map(MapAction),
+
+  /// An unordered set of unique values
+  Members(SetAction),
+
+  /// Several actions on the same field, applied in order. The steps are the value of the action.
+  Steps,
 }
 
 /// Actions specific to an ordered set of values
-#[derive(Clone, Debug, Serialize, Deserialize)]
+///
+/// The value being inserted or appended is the value of the PatchAction holding the list action. It
+/// is serialized alongside the index, such as `["List.Insert", [2, "Value"]]` or
+/// `["List.Append", "Value"]`. An update holds a PatchValue like a map update does, so a record in
+/// the list can be changed with a nested patch: `["List.Update", [2, {"Patch": ...}]]`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ListAction {
   /// Swap two values
   Swap(usize, usize),
@@ -239,19 +1049,42 @@ pub enum ListAction {
   /// Add a new item before the given index, Shifting items right
   Insert(usize),
 
-  /// Add the value(s) on to the end of the list
+  /// Add the value on to the end of the list
   Append(),
+
+  /// Take the value out from the first index and put it back in so it ends up at the second
+  Move(usize, usize),
+
+  /// Change the value at the index, either by replacing it or with a nested patch
+  Update(usize),
 }
 
 /// Actions specific to a set of key/value pairs
-#[derive(Clone, Debug, Serialize, Deserialize)]
+///
+/// Keys are the strings the map's keys serialize to. Like a ListAction, the value being inserted or
+/// updated is the value of the PatchAction, and it is serialized next to the key such as
+/// `["Map.Insert", ["Key", "Value"]]` or `["Map.Update", ["Key", {"Patch": ...}]]`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum MapAction {
-  /// Insert the value for the given key
+  /// Insert the value for the given key, which updates it instead if allow_upsert is set
   Insert(String),
 
-  /// Change the value for the existing key
+  /// Change the value for the existing key, either by replacing it or with a nested patch
   Update(String),
 
   /// Remove the key from the map
   Delete(String),
 }
+
+/// Actions specific to a set of unique values
+///
+/// The value being added or removed is the value of the PatchAction, serialized after the tag such as
+/// `["Set.Add", "Value"]` or `["Set.Remove", "Value"]`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SetAction {
+  /// Add the value to the set, which does nothing if it is already there and allow_upsert is set
+  Add,
+
+  /// Take the value out of the set
+  Remove,
+}
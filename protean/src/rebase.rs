@@ -0,0 +1,321 @@
+//! Moving a patch past another one made at the same time
+//!
+//! List actions point at elements by index, so a patch made against an older copy of a list hits the
+//! wrong elements once someone else has inserted or removed from it. Rebasing shifts the indices as if
+//! the patch had been made after the other one, in the style of operational transforms, so two people
+//! editing the same list end up with the same result whichever patch is applied first.
+
+use crate::{hash::content_hash, local::*};
+
+impl<'a> Patch<'a> {
+  /// Adjust this patch so it can be applied after onto, when both were made from the same base
+  ///
+  /// List indices are shifted past the inserts and removes in onto, including those in nested patches.
+  /// List and set actions on a field onto replaced entirely are dropped, as are edits to a map key onto
+  /// deleted and removing an element or set member that onto already removed. When both replace the
+  /// same value, the same one wins on either side. Sequences of steps are rebased one step at a time.
+  /// Edits that can't be ordered, like two swaps sharing an index or one side adding a set member the
+  /// other removed, return a RebaseConflict.
+  pub fn rebase(self, onto: &Patch) -> Result<Patch<'a>, ProteanError> {
+    rebase_patch(self, onto, "")
+  }
+}
+
+fn rebase_patch<'a>(
+  mut patch: Patch<'a>,
+  onto: &Patch,
+  prefix: &str,
+) -> Result<Patch<'a>, ProteanError> {
+  for theirs in onto.get_actions() {
+    let name = theirs.get_name();
+    let path = match prefix.is_empty() {
+      true => name.clone(),
+      false => format!("{}.{}", prefix, name),
+    };
+
+    if let Some(ours) = patch.remove(&name) {
+      if let Some(action) = rebase_action(ours, theirs, &path)? {
+        patch.add_action(action)?;
+      }
+    }
+  }
+  Ok(patch)
+}
+
+/// Rebase a single action, returning None if there is nothing left for it to do
+fn rebase_action<'a>(
+  ours: PatchAction<'a>,
+  theirs: &PatchAction,
+  path: &str,
+) -> Result<Option<PatchAction<'a>>, ProteanError> {
+  if ours.get_action() == &Action::Steps || theirs.get_action() == &Action::Steps {
+    return rebase_steps(ours, theirs, path);
+  }
+  let expected = ours.get_expected();
+
+  let action = match (ours.get_action().clone(), theirs.get_action()) {
+    // Like a list update, an edit to a key is dropped if the key was deleted
+    (Action::Map(MapAction::Update(key)), Action::Map(MapAction::Delete(other)))
+      if &key == other =>
+    {
+      return Ok(None)
+    }
+    (Action::Set, Action::Map(MapAction::Delete(key)))
+      if matches!(ours.get_value(), PatchValue::Patch(_)) =>
+    {
+      let mut patch = ours.into_value().into_patch()?;
+      patch.remove(key);
+      if patch.is_empty() {
+        return Ok(None);
+      }
+      PatchAction::from_value(Action::Set, PatchValue::Patch(patch), expected)
+    }
+
+    (Action::Set, Action::Set) | (Action::Map(MapAction::Update(_)), Action::Map(_)) => {
+      let nested_path = match ours.get_action() {
+        Action::Map(MapAction::Update(key)) => format!("{}.{}", path, key),
+        _ => path.to_string(),
+      };
+      match (ours.get_value(), theirs.get_value()) {
+        (PatchValue::Patch(_), PatchValue::Patch(right))
+          if ours.get_action() == theirs.get_action() =>
+        {
+          let act = ours.get_action().clone();
+          let patch = rebase_patch(ours.into_value().into_patch()?, right, &nested_path)?;
+          if patch.is_empty() {
+            return Ok(None);
+          }
+          PatchAction::from_value(act, PatchValue::Patch(patch), expected)
+        }
+        _ if ours.get_action() != theirs.get_action() => ours,
+
+        // Replacing the whole value wins over a nested patch, and between two whole values the one
+        // with the larger hash wins, so both sides keep the same one
+        (PatchValue::Value(_), PatchValue::Patch(_)) => ours,
+        (PatchValue::Patch(_), PatchValue::Value(_)) => return Ok(None),
+        (PatchValue::Value(left), PatchValue::Value(right)) => {
+          match content_hash(&left.as_json()?) > content_hash(&right.as_json()?) {
+            true => ours,
+            false => return Ok(None),
+          }
+        }
+        _ => ours,
+      }
+    }
+
+    // The list or set was replaced, which wins over any change made to the old one
+    (Action::List(_), Action::Set)
+    | (Action::List(_), Action::Clear)
+    | (Action::List(_), Action::Reset)
+    | (Action::Members(_), Action::Set)
+    | (Action::Members(_), Action::Clear)
+    | (Action::Members(_), Action::Reset) => return Ok(None),
+
+    // Set members have no position, so only changes to the same value interact
+    (Action::Members(act), Action::Members(other))
+      if ours.get_value().as_json()? == theirs.get_value().as_json()? =>
+    {
+      match &act == other {
+        true => return Ok(None),
+        false => return Err(ProteanError::RebaseConflict(path.to_string())),
+      }
+    }
+
+    (Action::List(act), Action::List(other)) => {
+      let ours_value = ours.get_value().as_json().ok();
+      let theirs_value = theirs.get_value().as_json().ok();
+      let conflict = || ProteanError::RebaseConflict(path.to_string());
+
+      let act = match (act, other.clone()) {
+        (ListAction::Append(), ListAction::Append()) if ours_value == theirs_value => {
+          ListAction::Append()
+        }
+        (ListAction::Append(), ListAction::Append()) => return Err(conflict()),
+        (act, ListAction::Append()) | (act @ ListAction::Append(), _) => act,
+
+        (ListAction::Insert(idx), ListAction::Insert(other)) => {
+          // Both inserted at the same place, so order by value to pick the same winner on each side
+          let after = other < idx
+            || (other == idx
+              && ours_value.map(|val| val.to_string()) > theirs_value.map(|val| val.to_string()));
+          ListAction::Insert(if after { idx + 1 } else { idx })
+        }
+        (ListAction::Insert(idx), ListAction::Remove(other)) => {
+          ListAction::Insert(if other < idx { idx - 1 } else { idx })
+        }
+        (ListAction::Remove(idx), ListAction::Insert(other)) => {
+          ListAction::Remove(if other <= idx { idx + 1 } else { idx })
+        }
+        (ListAction::Remove(idx), ListAction::Remove(other)) if idx == other => return Ok(None),
+        (ListAction::Remove(idx), ListAction::Remove(other)) => {
+          ListAction::Remove(if other < idx { idx - 1 } else { idx })
+        }
+
+        (ListAction::Swap(left, right), ListAction::Swap(a, b))
+          if (left, right) == (a, b) || (left, right) == (b, a) =>
+        {
+          return Ok(None)
+        }
+        (ListAction::Swap(left, right), ListAction::Swap(a, b)) => {
+          if [left, right].iter().any(|idx| *idx == a || *idx == b) {
+            return Err(conflict());
+          }
+          ListAction::Swap(left, right)
+        }
+        (ListAction::Swap(left, right), ListAction::Insert(other)) => {
+          let shift = |idx: usize| if other <= idx { idx + 1 } else { idx };
+          ListAction::Swap(shift(left), shift(right))
+        }
+        (ListAction::Swap(left, right), ListAction::Remove(other)) => {
+          if left == other || right == other {
+            return Err(conflict());
+          }
+          let shift = |idx: usize| if other < idx { idx - 1 } else { idx };
+          ListAction::Swap(shift(left), shift(right))
+        }
+
+        // A move is the element leaving its place and being put back in the list without it
+        (ListAction::Move(from, to), ListAction::Move(other, dest)) if from == other => {
+          match to == dest {
+            true => return Ok(None),
+            false => return Err(conflict()),
+          }
+        }
+        (ListAction::Move(from, to), ListAction::Move(other, dest)) => {
+          // Where both moved elements end up in the list without either of them, with the one that
+          // came first in the original list going first when they land in the same place
+          let ours_gap = to - usize::from(other - usize::from(other > from) < to);
+          let theirs_gap = dest - usize::from(from - usize::from(from > other) < dest);
+          let after = theirs_gap < ours_gap || (theirs_gap == ours_gap && other < from);
+          ListAction::Move(moved(from, other, dest), ours_gap + usize::from(after))
+        }
+        (ListAction::Move(from, to), ListAction::Insert(other)) => {
+          let gap = other - usize::from(other > from);
+          ListAction::Move(
+            from + usize::from(other <= from),
+            to + usize::from(gap <= to),
+          )
+        }
+        (ListAction::Insert(idx), ListAction::Move(from, to)) => {
+          let gap = idx - usize::from(idx > from);
+          ListAction::Insert(gap + usize::from(to < gap))
+        }
+        (ListAction::Move(from, _), ListAction::Remove(other)) if from == other => return Ok(None),
+        (ListAction::Move(from, to), ListAction::Remove(other)) => {
+          let removed = moved(other, from, to);
+          ListAction::Move(
+            from - usize::from(other < from),
+            to - usize::from(removed < to),
+          )
+        }
+        (ListAction::Remove(idx), ListAction::Move(from, to)) => {
+          ListAction::Remove(moved(idx, from, to))
+        }
+        (ListAction::Move(from, _), ListAction::Swap(a, b))
+        | (ListAction::Swap(a, b), ListAction::Move(from, _))
+          if from == a || from == b =>
+        {
+          return Err(conflict())
+        }
+        (ListAction::Move(from, to), ListAction::Swap(_, _)) => ListAction::Move(from, to),
+        (ListAction::Swap(a, b), ListAction::Move(from, to)) => {
+          ListAction::Swap(moved(a, from, to), moved(b, from, to))
+        }
+        (ListAction::Update(idx), ListAction::Move(from, to)) => {
+          ListAction::Update(moved(idx, from, to))
+        }
+
+        (ListAction::Update(idx), ListAction::Update(other)) if idx == other => {
+          match (ours.get_value(), theirs.get_value()) {
+            (PatchValue::Patch(_), PatchValue::Patch(right)) => {
+              let nested_path = format!("{}.{}", path, idx);
+              let patch = rebase_patch(ours.into_value().into_patch()?, right, &nested_path)?;
+              if patch.is_empty() {
+                return Ok(None);
+              }
+              let act = Action::List(ListAction::Update(idx));
+              return Ok(Some(PatchAction::from_value(
+                act,
+                PatchValue::Patch(patch),
+                expected,
+              )));
+            }
+            _ if ours_value.is_some() && ours_value == theirs_value => ListAction::Update(idx),
+            _ => return Err(conflict()),
+          }
+        }
+        // An update follows the element it changes, and is dropped if the element was removed
+        (ListAction::Update(idx), ListAction::Update(_)) => ListAction::Update(idx),
+        (ListAction::Update(idx), ListAction::Insert(other)) => {
+          ListAction::Update(if other <= idx { idx + 1 } else { idx })
+        }
+        (ListAction::Update(idx), ListAction::Remove(other)) if idx == other => return Ok(None),
+        (ListAction::Update(idx), ListAction::Remove(other)) => {
+          ListAction::Update(if other < idx { idx - 1 } else { idx })
+        }
+        (ListAction::Update(idx), ListAction::Swap(a, b)) => ListAction::Update(match idx {
+          idx if idx == a => b,
+          idx if idx == b => a,
+          idx => idx,
+        }),
+        (act, ListAction::Update(_)) => act,
+
+        // A swap moves elements around without changing the length
+        (ListAction::Insert(idx), ListAction::Swap(_, _)) => ListAction::Insert(idx),
+        (ListAction::Remove(idx), ListAction::Swap(a, b)) => {
+          if idx == a || idx == b {
+            return Err(conflict());
+          }
+          ListAction::Remove(idx)
+        }
+      };
+      PatchAction::from_value(Action::List(act), ours.into_value(), expected)
+    }
+
+    _ => ours,
+  };
+  Ok(Some(action))
+}
+
+/// Rebase a sequence of steps past another, one pair of steps at a time
+///
+/// Each of their steps is also moved past our earlier steps, since that is the list it will find
+/// when our later steps are rebased past it.
+fn rebase_steps<'a>(
+  ours: PatchAction<'a>,
+  theirs: &PatchAction,
+  path: &str,
+) -> Result<Option<PatchAction<'a>>, ProteanError> {
+  let name = ours.get_name();
+  let mut steps = ours.into_steps();
+  for step in theirs.try_clone()?.into_steps() {
+    let mut theirs = Some(step);
+    let mut rebased = Vec::new();
+    for ours in steps {
+      match &theirs {
+        Some(step) => {
+          let next = rebase_action(step.try_clone()?, &ours, path)?;
+          rebased.extend(rebase_action(ours, step, path)?);
+          theirs = next;
+        }
+        None => rebased.push(ours),
+      }
+    }
+    steps = rebased;
+  }
+
+  Ok(match steps.len() {
+    0 => None,
+    1 => steps.pop(),
+    _ => Some(PatchAction::from_steps(name, steps)),
+  })
+}
+
+/// Where the element at idx ends up after the one at from is moved to to
+fn moved(idx: usize, from: usize, to: usize) -> usize {
+  if idx == from {
+    return to;
+  }
+  let idx = idx - usize::from(idx > from);
+  idx + usize::from(idx >= to)
+}
@@ -0,0 +1,187 @@
+//! Patchwork for any serde type, without writing or deriving an impl
+//!
+//! Wrapping a value in `Serde` diffs and applies it by converting it to JSON and back. Fields are named
+//! by the keys serde gives them, so renames and flattening are followed. It is slower than a derived
+//! impl, but works for third party types that can't be derived on. The value must serialize as a map
+//! or struct.
+
+use crate::{
+  impls::{json, object, primitives},
+  local::*,
+};
+
+use serde_json::{Map, Value};
+
+/// A serde type that is patched through its JSON form
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Serde<T>(pub T);
+
+impl<T> Serde<T> {
+  pub fn into_inner(self) -> T {
+    self.0
+  }
+}
+
+impl<T> From<T> for Serde<T> {
+  fn from(value: T) -> Serde<T> {
+    Serde(value)
+  }
+}
+
+impl<T> std::ops::Deref for Serde<T> {
+  type Target = T;
+
+  fn deref(&self) -> &T {
+    &self.0
+  }
+}
+
+impl<T> std::ops::DerefMut for Serde<T> {
+  fn deref_mut(&mut self) -> &mut T {
+    &mut self.0
+  }
+}
+
+impl<T: Serialize> Serde<T> {
+  fn to_json(&self) -> Result<Map<String, Value>, ProteanError> {
+    match serde_json::to_value(&self.0)? {
+      Value::Object(obj) => Ok(obj),
+      _ => Err(ProteanError::InvalidPatchType),
+    }
+  }
+}
+
+/// Fields are diffed with `json::diff`, so nested values give nested patches and list actions
+///
+/// A field serde leaves out of the new value, like an Option skipped when it is None, is Reset. When
+/// applied this removes the key, leaving serde to fill in its default. The whole patch is applied to
+/// the JSON before it is converted back, so the value only has to be valid once every field is done.
+impl<'a, T> Patchwork<'a> for Serde<T>
+where
+  T: Clone + Serialize + DeserializeOwned,
+{
+  type Accessor = FieldName;
+  type Element = OwnedValue;
+
+  fn get_name() -> String {
+    std::any::type_name::<T>().to_string()
+  }
+
+  fn get_field(&'a self, name: FieldName) -> Result<OwnedValue, ProteanError> {
+    let value = self
+      .to_json()?
+      .remove(name.as_str())
+      .ok_or(ProteanError::FieldNotFound)?;
+    Ok(OwnedValue::new(name.to_string(), value))
+  }
+
+  fn values(&'a self) -> Result<Vec<OwnedValue>, ProteanError> {
+    Ok(
+      self
+        .to_json()?
+        .into_iter()
+        .map(|(name, value)| OwnedValue::new(name, value))
+        .collect(),
+    )
+  }
+
+  fn diff(&'a self, other: &'a Self) -> Result<Patch<'a>, ProteanError> {
+    let (old, new) = (self.to_json()?, other.to_json()?);
+    let mut patch = Self::new_patch();
+    for (name, value) in &old {
+      let action = match new.get(name) {
+        Some(other) => json::diff(name, value, other)?,
+        None => Some(PatchAction::new(
+          Action::Reset,
+          OwnedValue::new(name.clone(), Value::Null),
+          None,
+        )),
+      };
+      if let Some(action) = action {
+        patch.add_action(action)?;
+      }
+    }
+    for (name, value) in new {
+      if !old.contains_key(&name) {
+        patch.add_action(PatchAction::new(
+          Action::Set,
+          OwnedValue::new(name, value),
+          None,
+        ))?;
+      }
+    }
+    Ok(patch)
+  }
+
+  fn apply(&mut self, patch: Patch) -> Result<Patch<'a>, ProteanError> {
+    let mut fields = self.to_json()?;
+    let undo = primitives::apply_each(
+      &mut fields,
+      patch,
+      |fields, name| Ok(fields.get(name).cloned().unwrap_or_default()),
+      |fields, _, action| apply_entry(fields, action),
+    )?;
+    self.0 = serde_json::from_value(Value::Object(fields))?;
+
+    let mut reverse = Self::new_patch();
+    for action in undo {
+      reverse.add_action(action)?;
+    }
+    Ok(reverse)
+  }
+
+  fn apply_field(
+    &mut self,
+    field: FieldName,
+    action: PatchAction,
+  ) -> Result<PatchAction<'a>, ProteanError> {
+    let mut fields = self.to_json()?;
+    let undo = apply_entry(&mut fields, action).map_err(|err| err.within(field.as_str()))?;
+    self.0 = serde_json::from_value(Value::Object(fields))?;
+    Ok(undo)
+  }
+}
+
+/// Apply an action to the key it names, where Reset removes the key and Set can add it back
+fn apply_entry<'a>(
+  fields: &mut Map<String, Value>,
+  action: PatchAction,
+) -> Result<PatchAction<'a>, ProteanError> {
+  let name = action.get_name();
+  let undo =
+    |act: Action, value: Value| PatchAction::new(act, OwnedValue::new(name.clone(), value), None);
+
+  match (action.get_action(), action.get_value()) {
+    (Action::Reset, _) => Ok(match fields.remove(&name) {
+      Some(old) => undo(Action::Set, old),
+      None => undo(Action::Null, Value::Null),
+    }),
+    (Action::Set, PatchValue::Value(value)) if !fields.contains_key(&name) => {
+      fields.insert(name.clone(), value.as_json()?);
+      Ok(undo(Action::Reset, Value::Null))
+    }
+    _ => {
+      let field = fields.get_mut(&name).ok_or(ProteanError::FieldNotFound)?;
+      json::apply_action(field, action)
+    }
+  }
+}
+
+/// A Serde value nested in another struct is patched the same way as any other nested Patchwork
+impl<'a, T> Patchable<'a> for Serde<T>
+where
+  T: Clone + Debug + Serialize + DeserializeOwned,
+{
+  fn diff_value(
+    &'a self,
+    name: &str,
+    other: &'a Self,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError> {
+    object::diff_nested(name, self, other)
+  }
+
+  fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+    object::apply_object(self, action)
+  }
+}
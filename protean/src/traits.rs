@@ -1,11 +1,16 @@
 //! The core Patchwork trait and implementations
 
-use super::local::*;
+use super::{
+  impls::{json, primitives},
+  local::*,
+};
 
 /// The core trait,
-pub trait Patchwork<'a>: Clone + Sized + Serialize + Deserialize<'a> {
+pub trait Patchwork<'a>: Clone + Sized + Serialize + DeserializeOwned {
   /// A getter/setter key, how to target a portion of the current object for patching
-  type Accessor;
+  ///
+  /// This is parsed from the field name stored in a patch, so it can be found again when applying
+  type Accessor: FromStr<Err = ProteanError>;
 
   /// An enumeration of each field and a wrapper for the value
   ///
@@ -14,7 +19,9 @@ pub trait Patchwork<'a>: Clone + Sized + Serialize + Deserialize<'a> {
   type Element: Patchworthy<'a> + Serialize;
 
   /// Get an Id for the given object, if one is defined
-  fn get_id() -> Option<String> {
+  ///
+  /// Lists of records are matched up by their ids when diffed with `impls::list::diff_keyed`.
+  fn get_id(&self) -> Option<String> {
     None
   }
 
@@ -42,28 +49,103 @@ pub trait Patchwork<'a>: Clone + Sized + Serialize + Deserialize<'a> {
     Patch::new(Self::get_name())
   }
 
-  /// Apply a given patch
-  fn apply(&mut self, _patch: Patch) -> Result<Patch<'a>, ProteanError> {
-    todo!(
-      "Default Apply for {} is not yet written",
-      std::any::type_name::<Self>()
-    )
+  /// Apply a given patch, returning the patch that will undo it
+  ///
+  /// Each action is sent to its field through `apply_field`. If any of them fail, the fields that
+  /// were already changed are rolled back before returning the error, so the object is never left
+  /// partially patched. Actions with an expected hash are checked against the current value of the
+  /// field first, with the patch options deciding what happens when it doesn't match.
+  fn apply(&mut self, patch: Patch) -> Result<Patch<'a>, ProteanError> {
+    let undo = primitives::apply_each(
+      self,
+      patch,
+      |value, name| value.get_json(name),
+      |value, _, action| value.apply_action(action),
+    )?;
+
+    let mut reverse = Self::new_patch();
+    for action in undo {
+      reverse.add_action(action)?;
+    }
+    Ok(reverse)
   }
 
+  /// Get the current value of a field as JSON, which expected hashes are checked against
+  ///
+  /// By default this looks the field up in the serialized object, so it only finds fields whose name
+  /// in a patch is the same as their serde key. The derive reads each field directly instead.
+  fn get_json(&self, name: &str) -> Result<serde_json::Value, ProteanError> {
+    let value = serde_json::to_value(self)?;
+    Ok(json::child(&value, name).cloned().unwrap_or_default())
+  }
+
+  /// Perform an action against the field it names, returning the action that will undo it
+  ///
+  /// A sequence of steps is applied in order, and the steps that already ran are undone if a later
+  /// one fails.
+  fn apply_action(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError> {
+    let name = action.get_name();
+    if action.get_action() != &Action::Steps {
+      let field = name.parse::<Self::Accessor>()?;
+      return self.apply_field(field, action);
+    }
+
+    let mut undo = Vec::new();
+    for step in action.into_steps() {
+      match self.apply_action(step) {
+        Ok(step) => undo.push(step),
+        Err(err) => {
+          for step in undo.into_iter().rev() {
+            self.apply_action(step)?;
+          }
+          return Err(err);
+        }
+      }
+    }
+    undo.reverse();
+    Ok(PatchAction::from_steps(name, undo))
+  }
+
+  /// Perform a single action against the given field, returning the action that will undo it
+  ///
+  /// Most fields can hand this off to `impls::primitives::apply_value`, with nested Patchwork
+  /// structs using `impls::object::apply_nested` instead.
+  fn apply_field(
+    &mut self,
+    field: Self::Accessor,
+    action: PatchAction,
+  ) -> Result<PatchAction<'a>, ProteanError>;
+
   /// Export the full structure as a patch
   ///
   /// This is the same way that most databases will backup their data as a set of inserts instead of
   /// making a custom format.
-  fn as_patch(&'a self) -> Patch<'a> {
+  fn as_patch(&'a self) -> Result<Patch<'a>, ProteanError> {
     let mut patch = Patch::new(Self::get_name());
-    for field in self.values() {
-      patch.add(Action::Set, field, None).unwrap();
+    for field in self.values()? {
+      patch.add(Action::Set, field, None)?;
     }
-    patch
+    Ok(patch)
   }
 
   /// Return a Patchworthy list containing the value of each field
-  fn values(&'a self) -> Vec<Self::Field>;
+  ///
+  /// This can fail for impls that have to convert the object to find its fields.
+  fn values(&'a self) -> Result<Vec<Self::Element>, ProteanError>;
+
+  /// Compare two instances, returning a patch that will turn this one into the other
+  ///
+  /// Fields are paired up by position from `values`, so only the fields that changed will be in the
+  /// resulting patch. Comparing an object to itself returns an empty patch.
+  fn diff(&'a self, other: &'a Self) -> Result<Patch<'a>, ProteanError> {
+    let mut patch = Self::new_patch();
+    for (left, right) in self.values()?.into_iter().zip(other.values()?) {
+      if let Some(action) = left.diff(right)? {
+        patch.add_action(action)?;
+      }
+    }
+    Ok(patch)
+  }
 
   // Leave for later. This should be its own project and allow versioned patches to
   // migrate/ignore/force data to match the object being applied to
@@ -74,6 +156,24 @@ pub trait Patchwork<'a>: Clone + Sized + Serialize + Deserialize<'a> {
   // }
 }
 
+/// A value that can be compared and patched by itself, whichever struct it is stored in
+///
+/// Primitives and the standard collections implement this, so a struct made of them can use `Field`
+/// and `FieldName` rather than writing an enum for its fields.
+pub trait Patchable<'a>: Serialize + Debug {
+  /// Compare against the same field from another instance
+  ///
+  /// Returns the action needed to turn this value into the other one, or None if they are the same.
+  fn diff_value(
+    &'a self,
+    name: &str,
+    other: &'a Self,
+  ) -> Result<Option<PatchAction<'a>>, ProteanError>;
+
+  /// Perform an action against the value, returning the action that will undo it
+  fn apply_value(&mut self, action: PatchAction) -> Result<PatchAction<'a>, ProteanError>;
+}
+
 /// Annotation that tells patchwork it is an enumeration of a values
 ///
 /// There are optional option classes that can be customized based on the field, which can modify
@@ -99,6 +199,28 @@ pub trait Patchworthy<'a>: Send + Sync + Debug + Display {
   /// This is custom, as the enumeration wrapping the inner value needs to be dropped. Using Serde's
   /// JSON value removes a conversion step
   fn as_json(&self) -> Result<serde_json::Value, ProteanError>;
+
+  /// A hash of the value that is the same on every machine, for use as an expected value
+  ///
+  /// See the `hash` module for how it is calculated.
+  fn content_hash(&self) -> Result<u64, ProteanError> {
+    Ok(crate::hash::content_hash(&self.as_json()?))
+  }
+
+  /// Compare against the same field from another instance
+  ///
+  /// Returns the action needed to turn this value into the other one, or None if they are the
+  /// same. The default compares the JSON values and sets the whole field when they differ. Fields
+  /// holding a nested Patchwork should override this to return the nested diff instead.
+  fn diff(&self, other: Self) -> Result<Option<PatchAction<'a>>, ProteanError>
+  where
+    Self: Sized + 'a,
+  {
+    match self.as_json()? == other.as_json()? {
+      true => Ok(None),
+      false => Ok(Some(PatchAction::new(Action::Set, other, None))),
+    }
+  }
 }
 
 // A customizable set of actions that can be performed on a field.
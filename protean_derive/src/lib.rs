@@ -0,0 +1,462 @@
+//! Derive Patchwork for structs with named fields
+//!
+//! The derive writes the Accessor and Element enums a hand written impl would need, with a variant
+//! for each field. Every field type must be Patchable, which covers the primitives, the standard
+//! collections and wrappers, and any other struct deriving Patchwork. The struct itself must also
+//! implement Clone, Debug, Serialize and Deserialize.
+//!
+//! Fields can be configured with `#[protean(...)]`:
+//! - `skip` leaves the field out of diffs, and patches can't change it, even by replacing the whole
+//!   struct
+//! - `rename = "name"` uses a different name for the field in patches and its enum variants, which
+//!   doesn't need to match its serde name as expected hashes and inverses read the field directly
+//! - `key` marks the field returned by `get_id`, which must implement Display
+//! - `immutable` still diffs the field, but applying any action to it is an error, as is replacing
+//!   the whole struct with one where the field is different
+//! - `with = "module"` uses `module::diff` and `module::apply` instead of the field being Patchable,
+//!   with the same signatures as `Patchable::diff_value` and `Patchable::apply_value`
+
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+  parse_macro_input, Data, DeriveInput, Fields, GenericParam, Lifetime, LifetimeDef, Lit, Meta,
+  NestedMeta,
+};
+
+#[proc_macro_derive(Patchwork, attributes(protean))]
+pub fn derive_patchwork(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  match expand(input) {
+    Ok(tokens) => tokens.into(),
+    Err(err) => err.to_compile_error().into(),
+  }
+}
+
+/// A field of the struct, with the names it is given in the patch and the generated enums
+struct PatchField {
+  ident: Ident,
+  variant: Ident,
+  name: String,
+  ty: syn::Type,
+  skip: bool,
+  key: bool,
+  immutable: bool,
+  with: Option<syn::Path>,
+}
+
+impl PatchField {
+  fn new(field: &syn::Field) -> syn::Result<PatchField> {
+    let ident = field.ident.clone().expect("Only named fields are patched");
+    let mut patch_field = PatchField {
+      variant: ident.clone(),
+      name: unraw(&ident),
+      ident,
+      ty: field.ty.clone(),
+      skip: false,
+      key: false,
+      immutable: false,
+      with: None,
+    };
+
+    for attr in field
+      .attrs
+      .iter()
+      .filter(|attr| attr.path.is_ident("protean"))
+    {
+      let list = match attr.parse_meta()? {
+        Meta::List(list) => list,
+        meta => return Err(syn::Error::new_spanned(meta, "Expected #[protean(...)]")),
+      };
+      for nested in list.nested {
+        match nested {
+          NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => patch_field.skip = true,
+          NestedMeta::Meta(Meta::Path(path)) if path.is_ident("key") => patch_field.key = true,
+          NestedMeta::Meta(Meta::Path(path)) if path.is_ident("immutable") => {
+            patch_field.immutable = true
+          }
+          NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("rename") => {
+            patch_field.name = string_value(&pair.lit)?;
+          }
+          NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("with") => {
+            patch_field.with = Some(syn::parse_str(&string_value(&pair.lit)?)?);
+          }
+          nested => {
+            return Err(syn::Error::new_spanned(
+              nested,
+              "Unknown protean attribute, expected skip, rename, key, immutable or with",
+            ))
+          }
+        }
+      }
+    }
+
+    // The variant follows the name used in patches, so renaming a field also renames its variant
+    let variant = camel_case(&patch_field.name);
+    patch_field.variant = match variant.starts_with(|c: char| c.is_alphabetic()) {
+      true => Ident::new(&variant, patch_field.ident.span()),
+      false => {
+        return Err(syn::Error::new_spanned(
+          &patch_field.ident,
+          format!(
+            "Can't make an enum variant from the field name `{}`",
+            patch_field.name
+          ),
+        ))
+      }
+    };
+    Ok(patch_field)
+  }
+}
+
+/// The name of a field without the prefix of a raw identifier
+fn unraw(ident: &Ident) -> String {
+  ident.to_string().trim_start_matches("r#").to_string()
+}
+
+fn string_value(lit: &Lit) -> syn::Result<String> {
+  match lit {
+    Lit::Str(value) => Ok(value.value()),
+    lit => Err(syn::Error::new_spanned(lit, "Expected a string")),
+  }
+}
+
+/// Turn a snake case field name into the name of its enum variant
+///
+/// Any character that can't be in an identifier is treated like an underscore.
+fn camel_case(name: &str) -> String {
+  name
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|part| !part.is_empty())
+    .map(|part| {
+      let mut chars = part.chars();
+      match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+      }
+    })
+    .collect()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+  let fields = match &input.data {
+    Data::Struct(data) => match &data.fields {
+      Fields::Named(fields) => fields
+        .named
+        .iter()
+        .map(PatchField::new)
+        .collect::<syn::Result<Vec<_>>>()?,
+      _ => {
+        return Err(syn::Error::new_spanned(
+          &input.ident,
+          "Patchwork can only be derived for structs with named fields",
+        ))
+      }
+    },
+    _ => {
+      return Err(syn::Error::new_spanned(
+        &input.ident,
+        "Patchwork can only be derived for structs",
+      ))
+    }
+  };
+
+  let get_id = match fields.iter().filter(|field| field.key).collect::<Vec<_>>()[..] {
+    [] => quote!(),
+    [field] => {
+      let ident = &field.ident;
+      quote! {
+        fn get_id(&self) -> Option<String> {
+          Some(::std::string::ToString::to_string(&self.#ident))
+        }
+      }
+    }
+    [_, field, ..] => {
+      return Err(syn::Error::new_spanned(
+        &field.ident,
+        "Only one field can be the key",
+      ))
+    }
+  };
+  let (skipped, fields): (Vec<_>, Vec<_>) = fields.into_iter().partition(|field| field.skip);
+
+  // Different field names can end up with the same variant or patch name, such as a_b and a__b
+  for (idx, field) in fields.iter().enumerate() {
+    if let Some(other) = fields[..idx]
+      .iter()
+      .find(|other| other.variant == field.variant || other.name == field.name)
+    {
+      return Err(syn::Error::new_spanned(
+        &field.ident,
+        format!(
+          "The field `{}` has the same patch name or variant as `{}`, rename one of them",
+          unraw(&field.ident),
+          unraw(&other.ident)
+        ),
+      ));
+    }
+  }
+
+  let vis = &input.vis;
+  let name = &input.ident;
+  let accessor = format_ident!("{}Accessor", name);
+  let element = format_ident!("{}Field", name);
+
+  // The elements borrow from the struct, so they need a lifetime of their own
+  let lifetime = Lifetime::new("'protean", Span::call_site());
+  let mut generics = input.generics.clone();
+  generics.params.insert(
+    0,
+    GenericParam::Lifetime(LifetimeDef::new(lifetime.clone())),
+  );
+  let element_params = &generics.params;
+  let element_where = &input.generics.where_clause;
+
+  let (_, ty_generics, _) = input.generics.split_for_impl();
+  let mut bounded = generics.clone();
+  let predicates = &mut bounded.make_where_clause().predicates;
+  for param in input.generics.type_params() {
+    let ident = &param.ident;
+    predicates.push(syn::parse_quote!(#ident: #lifetime));
+  }
+  predicates.push(syn::parse_quote!(
+    #name #ty_generics: Clone
+      + ::std::fmt::Debug
+      + ::protean::__private::serde::Serialize
+      + ::protean::__private::serde::de::DeserializeOwned
+  ));
+  for field in &fields {
+    let ty = &field.ty;
+    predicates.push(match field.with {
+      Some(_) => syn::parse_quote!(
+        #ty: ::std::fmt::Debug + ::protean::__private::serde::Serialize + Sync
+      ),
+      None => syn::parse_quote!(#ty: ::protean::prelude::Patchable<#lifetime> + Sync),
+    });
+  }
+  let (impl_generics, elem_generics, where_clause) = bounded.split_for_impl();
+
+  let idents: Vec<_> = fields.iter().map(|field| &field.ident).collect();
+  let variants: Vec<_> = fields.iter().map(|field| &field.variant).collect();
+  let names: Vec<_> = fields.iter().map(|field| &field.name).collect();
+  let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+  let diffs: Vec<_> = fields
+    .iter()
+    .map(|field| {
+      let name = &field.name;
+      match &field.with {
+        Some(with) => quote!(#with::diff(#name, *left, right)),
+        None => quote!(::protean::prelude::Patchable::diff_value(*left, #name, right)),
+      }
+    })
+    .collect();
+  let applies: Vec<_> = fields
+    .iter()
+    .map(|field| {
+      let ident = &field.ident;
+      match (&field.with, field.immutable) {
+        (_, true) => quote! {
+          Err(::protean::prelude::ProteanError::ImmutableField(action.get_name()))
+        },
+        (Some(with), _) => quote!(#with::apply(&mut self.#ident, action)),
+        (None, _) => quote!(::protean::prelude::Patchable::apply_value(&mut self.#ident, action)),
+      }
+    })
+    .collect();
+  // Replacing the whole struct doesn't go through apply_field, so immutable fields are compared
+  // before and after instead, and skipped fields are put back
+  let immutable: Vec<_> = fields.iter().filter(|field| field.immutable).collect();
+  let apply_value = match immutable.is_empty() {
+    true => quote!(::protean::impls::object::apply_object(self, action)),
+    false => {
+      let idents = immutable.iter().map(|field| &field.ident);
+      let after = idents.clone();
+      let names = immutable.iter().map(|field| &field.name);
+      quote! {
+        let before = vec![#(::protean::__private::serde_json::to_value(&self.#idents)?),*];
+        let undo = ::protean::impls::object::apply_object(self, action)?;
+        let after = vec![#(::protean::__private::serde_json::to_value(&self.#after)?),*];
+        match before.iter().zip(&after).position(|(before, after)| before != after) {
+          Some(idx) => {
+            ::protean::impls::object::apply_object(self, undo)?;
+            Err(::protean::prelude::ProteanError::ImmutableField([#(#names),*][idx].to_string()))
+          }
+          None => Ok(undo),
+        }
+      }
+    }
+  };
+  let apply_value = match skipped.is_empty() {
+    true => apply_value,
+    false => {
+      let idents = skipped.iter().map(|field| &field.ident);
+      quote! {
+        let kept = ::std::clone::Clone::clone(self);
+        let undo = { #apply_value }?;
+        #(self.#idents = kept.#idents;)*
+        Ok(undo)
+      }
+    }
+  };
+  // Without any fields the lifetime and generics would go unused, so the elements get a variant
+  // that can't be built instead, and every match on a field is unreachable
+  let (unused, unused_arm, allow_unreachable) = match fields.is_empty() {
+    true => (
+      quote! {
+        #[doc(hidden)]
+        __Unused(
+          ::std::convert::Infallible,
+          ::std::marker::PhantomData<&#lifetime #name #ty_generics>,
+        ),
+      },
+      quote!(#element::__Unused(never, _) => match *never {},),
+      quote!(#[allow(unreachable_code)]),
+    ),
+    false => (quote!(), quote!(), quote!()),
+  };
+  let accessor_doc = format!("The fields of {} that can be patched", name);
+  let element_doc = format!("A reference to the value of one of the fields of {}", name);
+
+  Ok(quote! {
+    #[doc = #accessor_doc]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    #vis enum #accessor {
+      #(#variants,)*
+    }
+
+    impl ::std::str::FromStr for #accessor {
+      type Err = ::protean::prelude::ProteanError;
+
+      fn from_str(name: &str) -> Result<#accessor, ::protean::prelude::ProteanError> {
+        match name {
+          #(#names => Ok(#accessor::#variants),)*
+          _ => Err(::protean::prelude::ProteanError::FieldNotFound),
+        }
+      }
+    }
+
+    #[doc = #element_doc]
+    #[derive(Debug, Clone)]
+    #vis enum #element<#element_params> #element_where {
+      #(#variants(&#lifetime #types),)*
+      #unused
+    }
+
+    #allow_unreachable
+    impl #impl_generics ::protean::prelude::Patchworthy<#lifetime> for #element #elem_generics
+    #where_clause
+    {
+      fn get_field_name(&self) -> String {
+        match self {
+          #(#element::#variants(_) => #names.to_string(),)*
+          #unused_arm
+        }
+      }
+
+      fn as_json(&self) -> Result<::protean::__private::serde_json::Value, ::protean::prelude::ProteanError> {
+        Ok(match self {
+          #(#element::#variants(value) => ::protean::__private::serde_json::to_value(value)?,)*
+          #unused_arm
+        })
+      }
+
+      #[allow(unreachable_patterns)]
+      fn diff(
+        &self,
+        other: Self,
+      ) -> Result<Option<::protean::prelude::PatchAction<#lifetime>>, ::protean::prelude::ProteanError> {
+        match (self, other) {
+          #((#element::#variants(left), #element::#variants(right)) => #diffs,)*
+          _ => Err(::protean::prelude::ProteanError::InvalidPatchType),
+        }
+      }
+    }
+
+    impl #impl_generics ::std::fmt::Display for #element #elem_generics #where_clause {
+      fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        match self {
+          #(#element::#variants(value) => write!(f, "{}: {:?}", #names, value),)*
+          #unused_arm
+        }
+      }
+    }
+
+    #allow_unreachable
+    impl #impl_generics ::protean::__private::serde::Serialize for #element #elem_generics
+    #where_clause
+    {
+      fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+      where
+        S: ::protean::__private::serde::Serializer,
+      {
+        use ::protean::__private::serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(1))?;
+        match self {
+          #(#element::#variants(value) => map.serialize_entry(#names, value)?,)*
+          #unused_arm
+        }
+        map.end()
+      }
+    }
+
+    #allow_unreachable
+    impl #impl_generics ::protean::prelude::Patchwork<#lifetime> for #name #ty_generics #where_clause {
+      type Accessor = #accessor;
+      type Element = #element #elem_generics;
+
+      #get_id
+
+      fn get_field(
+        &#lifetime self,
+        name: #accessor,
+      ) -> Result<#element #elem_generics, ::protean::prelude::ProteanError> {
+        Ok(match name {
+          #(#accessor::#variants => #element::#variants(&self.#idents),)*
+        })
+      }
+
+      fn get_json(
+        &self,
+        name: &str,
+      ) -> Result<::protean::__private::serde_json::Value, ::protean::prelude::ProteanError> {
+        Ok(match name.parse::<#accessor>()? {
+          #(#accessor::#variants => ::protean::__private::serde_json::to_value(&self.#idents)?,)*
+        })
+      }
+
+      fn values(
+        &#lifetime self,
+      ) -> Result<Vec<#element #elem_generics>, ::protean::prelude::ProteanError> {
+        Ok(vec![#(#element::#variants(&self.#idents)),*])
+      }
+
+      fn apply_field(
+        &mut self,
+        field: #accessor,
+        action: ::protean::prelude::PatchAction,
+      ) -> Result<::protean::prelude::PatchAction<#lifetime>, ::protean::prelude::ProteanError> {
+        match field {
+          #(#accessor::#variants => #applies,)*
+        }
+      }
+    }
+
+    impl #impl_generics ::protean::prelude::Patchable<#lifetime> for #name #ty_generics #where_clause {
+      fn diff_value(
+        &#lifetime self,
+        name: &str,
+        other: &#lifetime Self,
+      ) -> Result<Option<::protean::prelude::PatchAction<#lifetime>>, ::protean::prelude::ProteanError> {
+        ::protean::impls::object::diff_nested(name, self, other)
+      }
+
+      fn apply_value(
+        &mut self,
+        action: ::protean::prelude::PatchAction,
+      ) -> Result<::protean::prelude::PatchAction<#lifetime>, ::protean::prelude::ProteanError> {
+        #apply_value
+      }
+    }
+  })
+}
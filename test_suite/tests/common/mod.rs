@@ -27,7 +27,15 @@ macro_rules! test_fn {
 pub(crate) use test_fn;
 
 pub(crate) mod local {
-  pub use protean::prelude::*;
+  pub use protean::{
+    impls::{
+      list::{apply_list, diff_list},
+      map::apply_map,
+      object::apply_nested,
+      primitives::apply_value,
+    },
+    prelude::*,
+  };
 
   pub use rand::{distributions::Alphanumeric, prelude::*};
   pub use serde::{Deserialize, Serialize};
@@ -35,31 +43,25 @@ pub(crate) mod local {
     collections::HashMap,
     fmt::{Debug, Display},
     hash::{Hash, Hasher},
+    str::FromStr,
   };
   pub use uuid::Uuid;
 }
 
-/*
+// Only some of the test files use each set of models
+#[allow(dead_code)]
 pub(crate) mod tester {
   use super::local::*;
 
-  // pub use super::{Nested, Unkeyed};
-
-  #[derive(Debug, Clone)]
+  /// A struct with a spread of the data types that Patchwork should know how to handle
+  #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
   pub struct Tester {
     pub pk: Uuid,
     pub integer: i32,
     pub float: f32,
     pub string: String,
-    // pub nested: Nested,
-    // pub unkeyed: Unkeyed,
-  }
-
-  pub enum _TesterFields {
-    Pk(Uuid),
-    Integer(i32),
-    Float(f32),
-    String(String),
+    pub list: Vec<i32>,
+    pub nested: Nested,
   }
 
   impl Default for Tester {
@@ -69,14 +71,14 @@ pub(crate) mod tester {
         integer: 0,
         float: 0.0,
         string: "".to_string(),
-        // nested: Default::default(),
-        // unkeyed: Default::default(),
+        list: Vec::new(),
+        nested: Default::default(),
       }
     }
   }
 
   impl Tester {
-    pub fn _random() -> Tester {
+    pub fn random() -> Tester {
       let mut rng = rand::thread_rng();
       Tester {
         pk: uuid::Uuid::new_v4(),
@@ -89,14 +91,248 @@ pub(crate) mod tester {
             .map(char::from)
             .collect()
         },
-        // nested: Nested::random(),
-        // unkeyed: Unkeyed::random(),
+        list: (0..rng.gen_range(0..10)).map(|_| rng.gen()).collect(),
+        nested: Nested::random(),
+      }
+    }
+  }
+
+  impl<'a> Patchwork<'a> for Tester {
+    type Accessor = TesterAccessor;
+    type Element = TesterField<'a>;
+
+    fn get_id(&self) -> Option<String> {
+      Some(self.pk.to_string())
+    }
+
+    fn get_field(&'a self, name: TesterAccessor) -> Result<TesterField<'a>, ProteanError> {
+      Ok(match name {
+        TesterAccessor::Pk => TesterField::Pk(&self.pk),
+        TesterAccessor::Integer => TesterField::Integer(&self.integer),
+        TesterAccessor::Float => TesterField::Float(&self.float),
+        TesterAccessor::String => TesterField::String(&self.string),
+        TesterAccessor::List => TesterField::List(&self.list),
+        TesterAccessor::Nested => TesterField::Nested(&self.nested),
+      })
+    }
+
+    fn values(&'a self) -> Result<Vec<TesterField<'a>>, ProteanError> {
+      Ok(vec![
+        TesterField::Pk(&self.pk),
+        TesterField::Integer(&self.integer),
+        TesterField::Float(&self.float),
+        TesterField::String(&self.string),
+        TesterField::List(&self.list),
+        TesterField::Nested(&self.nested),
+      ])
+    }
+
+    fn apply_field(
+      &mut self,
+      field: TesterAccessor,
+      action: PatchAction,
+    ) -> Result<PatchAction<'a>, ProteanError> {
+      match field {
+        TesterAccessor::Pk => apply_value(&mut self.pk, action),
+        TesterAccessor::Integer => apply_value(&mut self.integer, action),
+        TesterAccessor::Float => apply_value(&mut self.float, action),
+        TesterAccessor::String => apply_value(&mut self.string, action),
+        TesterAccessor::List => apply_list(&mut self.list, action),
+        TesterAccessor::Nested => apply_nested(&mut self.nested, action),
+      }
+    }
+  }
+
+  pub enum TesterAccessor {
+    Pk,
+    Integer,
+    Float,
+    String,
+    List,
+    Nested,
+  }
+
+  impl FromStr for TesterAccessor {
+    type Err = ProteanError;
+
+    fn from_str(name: &str) -> Result<TesterAccessor, ProteanError> {
+      Ok(match name {
+        "pk" => TesterAccessor::Pk,
+        "integer" => TesterAccessor::Integer,
+        "float" => TesterAccessor::Float,
+        "string" => TesterAccessor::String,
+        "list" => TesterAccessor::List,
+        "nested" => TesterAccessor::Nested,
+        _ => return Err(ProteanError::FieldNotFound),
+      })
+    }
+  }
+
+  #[derive(Debug, Clone, Serialize)]
+  pub enum TesterField<'a> {
+    Pk(&'a Uuid),
+    Integer(&'a i32),
+    Float(&'a f32),
+    String(&'a String),
+    List(&'a Vec<i32>),
+    Nested(&'a Nested),
+  }
+
+  impl<'a> Patchworthy<'a> for TesterField<'a> {
+    fn get_field_name(&self) -> String {
+      match self {
+        TesterField::Pk(_) => "pk",
+        TesterField::Integer(_) => "integer",
+        TesterField::Float(_) => "float",
+        TesterField::String(_) => "string",
+        TesterField::List(_) => "list",
+        TesterField::Nested(_) => "nested",
+      }
+      .to_string()
+    }
+
+    fn as_json(&self) -> Result<serde_json::Value, ProteanError> {
+      Ok(match self {
+        TesterField::Pk(val) => serde_json::to_value(val),
+        TesterField::Integer(val) => serde_json::to_value(val),
+        TesterField::Float(val) => serde_json::to_value(val),
+        TesterField::String(val) => serde_json::to_value(val),
+        TesterField::List(val) => serde_json::to_value(val),
+        TesterField::Nested(val) => serde_json::to_value(val),
+      }?)
+    }
+
+    fn diff(&self, other: Self) -> Result<Option<PatchAction<'a>>, ProteanError> {
+      match (self, &other) {
+        (TesterField::Nested(left), TesterField::Nested(right)) => {
+          let patch = left.diff(right)?;
+          return Ok(match patch.is_empty() {
+            true => None,
+            false => Some(PatchAction::from_patch(self.get_field_name(), patch)),
+          });
+        }
+        (TesterField::List(left), TesterField::List(right)) => {
+          return diff_list(&self.get_field_name(), *left, *right)
+        }
+        _ => (),
+      }
+      match self.as_json()? == other.as_json()? {
+        true => Ok(None),
+        false => Ok(Some(PatchAction::new(Action::Set, other, None))),
+      }
+    }
+  }
+
+  impl<'a> Display for TesterField<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      write!(f, "{:?}", self)
+    }
+  }
+
+  /// A second struct to be nested inside the Tester
+  #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+  pub struct Nested {
+    pub pk: Uuid,
+    pub level_2: u8,
+  }
+
+  impl Default for Nested {
+    fn default() -> Nested {
+      Nested {
+        pk: uuid::Uuid::new_v4(),
+        level_2: 0,
       }
     }
   }
+
+  impl Nested {
+    pub fn random() -> Nested {
+      let mut rng = rand::thread_rng();
+      Nested {
+        pk: uuid::Uuid::new_v4(),
+        level_2: rng.gen(),
+      }
+    }
+  }
+
+  impl<'a> Patchwork<'a> for Nested {
+    type Accessor = NestedAccessor;
+    type Element = NestedField<'a>;
+
+    fn get_field(&'a self, name: NestedAccessor) -> Result<NestedField<'a>, ProteanError> {
+      Ok(match name {
+        NestedAccessor::Pk => NestedField::Pk(&self.pk),
+        NestedAccessor::Level2 => NestedField::Level2(&self.level_2),
+      })
+    }
+
+    fn values(&'a self) -> Result<Vec<NestedField<'a>>, ProteanError> {
+      Ok(vec![
+        NestedField::Pk(&self.pk),
+        NestedField::Level2(&self.level_2),
+      ])
+    }
+
+    fn apply_field(
+      &mut self,
+      field: NestedAccessor,
+      action: PatchAction,
+    ) -> Result<PatchAction<'a>, ProteanError> {
+      match field {
+        NestedAccessor::Pk => apply_value(&mut self.pk, action),
+        NestedAccessor::Level2 => apply_value(&mut self.level_2, action),
+      }
+    }
+  }
+
+  pub enum NestedAccessor {
+    Pk,
+    Level2,
+  }
+
+  impl FromStr for NestedAccessor {
+    type Err = ProteanError;
+
+    fn from_str(name: &str) -> Result<NestedAccessor, ProteanError> {
+      Ok(match name {
+        "pk" => NestedAccessor::Pk,
+        "level_2" => NestedAccessor::Level2,
+        _ => return Err(ProteanError::FieldNotFound),
+      })
+    }
+  }
+
+  #[derive(Debug, Clone, Serialize)]
+  pub enum NestedField<'a> {
+    Pk(&'a Uuid),
+    Level2(&'a u8),
+  }
+
+  impl<'a> Patchworthy<'a> for NestedField<'a> {
+    fn get_field_name(&self) -> String {
+      match self {
+        NestedField::Pk(_) => "pk",
+        NestedField::Level2(_) => "level_2",
+      }
+      .to_string()
+    }
+
+    fn as_json(&self) -> Result<serde_json::Value, ProteanError> {
+      Ok(match self {
+        NestedField::Pk(val) => serde_json::to_value(val),
+        NestedField::Level2(val) => serde_json::to_value(val),
+      }?)
+    }
+  }
+
+  impl<'a> Display for NestedField<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+      write!(f, "{:?}", self)
+    }
+  }
 }
-*/
 
+#[allow(dead_code, unused_imports)]
 pub(crate) mod database {
   use super::local;
 
@@ -116,28 +352,55 @@ pub(crate) mod database {
     }
 
     impl<'a> Patchwork<'a> for Db {
-      type Accessor = ();
+      type Accessor = DbAccessor;
       type Element = DbField<'a>;
 
       /// Get an Id for the given object
-      fn get_id() -> Option<String> {
+      fn get_id(&self) -> Option<String> {
         Some("TestDb".to_string())
       }
 
-      fn values(&'a self) -> Vec<Self::Child> {
-        vec![
+      fn get_field(&'a self, name: DbAccessor) -> Result<DbField<'a>, ProteanError> {
+        Ok(match name {
+          DbAccessor::Organizations => DbField::Organizations(&self.organizations),
+          DbAccessor::Addresses => DbField::Addresses(&self.addresses),
+        })
+      }
+
+      fn values(&'a self) -> Result<Vec<DbField<'a>>, ProteanError> {
+        Ok(vec![
           DbField::Organizations(&self.organizations),
           DbField::Addresses(&self.addresses),
-        ]
+        ])
       }
 
-      // fn get_child(&'a self, name: &str) -> Result<Self::Child, ProteanError> {
-      //   Ok(match name {
-      //     "addresses" => DbField::Addresses(&self.addresses),
-      //     "organizations" => DbField::Organizations(&self.organizations),
-      //     _ => return Err(ProteanError::FieldNotFound),
-      //   })
-      // }
+      fn apply_field(
+        &mut self,
+        field: DbAccessor,
+        action: PatchAction,
+      ) -> Result<PatchAction<'a>, ProteanError> {
+        match field {
+          DbAccessor::Organizations => apply_map(&mut self.organizations, action),
+          DbAccessor::Addresses => apply_map(&mut self.addresses, action),
+        }
+      }
+    }
+
+    pub enum DbAccessor {
+      Organizations,
+      Addresses,
+    }
+
+    impl FromStr for DbAccessor {
+      type Err = ProteanError;
+
+      fn from_str(name: &str) -> Result<DbAccessor, ProteanError> {
+        Ok(match name {
+          "organizations" => DbAccessor::Organizations,
+          "addresses" => DbAccessor::Addresses,
+          _ => return Err(ProteanError::FieldNotFound),
+        })
+      }
     }
 
     #[derive(Debug, Clone, Serialize)]
@@ -147,13 +410,13 @@ pub(crate) mod database {
     }
 
     impl<'a> Patchworthy<'a> for DbField<'a> {
-      // fn get_child_name(&self) -> String {
-      //   match self {
-      //     DbField::Organizations(_) => "Organizations",
-      //     DbField::Addresses(_) => "Addresses",
-      //   }
-      //   .to_string()
-      // }
+      fn get_field_name(&self) -> String {
+        match self {
+          DbField::Organizations(_) => "organizations",
+          DbField::Addresses(_) => "addresses",
+        }
+        .to_string()
+      }
 
       fn as_json(&self) -> Result<serde_json::Value, ProteanError> {
         Ok(match self {
@@ -0,0 +1,384 @@
+//! Deriving Patchwork instead of writing the field enums by hand
+
+mod common;
+
+use common::test_fn;
+
+use protean::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Patchwork)]
+pub struct Model {
+  id: Uuid,
+  count: i32,
+  name: String,
+  nickname: Option<String>,
+  scores: Vec<u8>,
+  tags: HashSet<String>,
+  limits: BTreeMap<String, u32>,
+  inner: Inner,
+  r#type: (char, bool),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Patchwork)]
+pub struct Inner {
+  level_2: u8,
+  deeper: Option<Wrapper<f64>>,
+}
+
+/// Generic structs get the same impls, as long as their fields are Patchable
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Patchwork)]
+pub struct Wrapper<T> {
+  value: T,
+}
+
+fn model() -> Model {
+  Model {
+    id: Uuid::new_v4(),
+    count: 1,
+    name: "first".to_string(),
+    nickname: None,
+    scores: vec![1, 2, 3, 4],
+    tags: ["a", "b"].iter().map(|tag| tag.to_string()).collect(),
+    limits: vec![("cpu".to_string(), 2)].into_iter().collect(),
+    inner: Inner {
+      level_2: 0,
+      deeper: Some(Wrapper { value: 0.5 }),
+    },
+    r#type: ('x', false),
+  }
+}
+
+test_fn!(
+  fn derived_fields() {
+    use std::str::FromStr;
+
+    let value = model();
+    assert!(ModelAccessor::from_str("level_2").is_err());
+    assert_eq!(
+      InnerAccessor::from_str("level_2").unwrap(),
+      InnerAccessor::Level2
+    );
+    assert_eq!(
+      ModelAccessor::from_str("type").unwrap(),
+      ModelAccessor::Type
+    );
+
+    let names: Vec<_> = value
+      .values()
+      .unwrap()
+      .iter()
+      .map(Patchworthy::get_field_name)
+      .collect();
+    assert_eq!(
+      names,
+      ["id", "count", "name", "nickname", "scores", "tags", "limits", "inner", "type"]
+    );
+
+    let field = value.get_field(ModelAccessor::Count).unwrap();
+    assert_eq!(serde_json::to_value(&field).unwrap(), json!({ "count": 1 }));
+    assert_eq!(field.as_json().unwrap(), json!(1));
+  }
+);
+
+test_fn!(
+  fn derived_diff_and_apply() {
+    let old = model();
+    let mut new = old.clone();
+    new.count = 2;
+    new.nickname = Some("nick".to_string());
+    new.scores.remove(1);
+    new.tags.insert("c".to_string());
+    new.limits.insert("disk".to_string(), 10);
+    new.inner.level_2 = 3;
+    new.inner.deeper = Some(Wrapper { value: 1.5 });
+    new.r#type.1 = true;
+
+    let patch = old.diff(&new).unwrap();
+    let serialized = serde_json::to_value(&patch).unwrap();
+    let actions = &serialized["actions"];
+    assert_eq!(actions["count"], json!(["Set", { "Value": 2 }]));
+    assert_eq!(actions["scores"], json!(["List.Remove", 1]));
+    assert_eq!(actions["tags"], json!(["Set.Add", "c"]));
+    assert_eq!(actions["limits"], json!(["Map.Insert", ["disk", 10]]));
+    assert_eq!(
+      actions["inner"][1]["Patch"]["actions"]["deeper"][1]["Patch"]["actions"],
+      json!({ "value": ["Set", { "Value": 1.5 }] })
+    );
+    assert!(actions.get("id").is_none());
+
+    // Round trip through serde, then apply and undo
+    let patch: Patch = serde_json::from_value(serialized).unwrap();
+    let mut target = old.clone();
+    let undo = target.apply(patch).unwrap();
+    assert_eq!(target, new);
+    target.apply(undo).unwrap();
+    assert_eq!(target, old);
+    assert!(old.diff(&old.clone()).unwrap().is_empty());
+  }
+);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Patchwork)]
+pub struct Account {
+  #[protean(key, immutable)]
+  id: u32,
+  #[protean(rename = "userName")]
+  user_name: String,
+  #[protean(skip)]
+  cache: Vec<u8>,
+  #[protean(with = "protean::impls::list::keyed")]
+  members: Vec<Member>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Patchwork)]
+pub struct Member {
+  #[protean(key)]
+  pk: u32,
+  role: String,
+}
+
+fn account() -> Account {
+  let member = |pk: u32, role: &str| Member {
+    pk,
+    role: role.to_string(),
+  };
+  Account {
+    id: 7,
+    user_name: "dave".to_string(),
+    cache: vec![1, 2],
+    members: vec![member(1, "owner"), member(2, "dev"), member(3, "qa")],
+  }
+}
+
+test_fn!(
+  fn derived_attributes() {
+    use std::str::FromStr;
+
+    let old = account();
+    assert_eq!(old.get_id(), Some("7".to_string()));
+    assert_eq!(old.members[1].get_id(), Some("2".to_string()));
+    assert_eq!(
+      AccountAccessor::from_str("userName").unwrap(),
+      AccountAccessor::UserName
+    );
+    assert!(AccountAccessor::from_str("user_name").is_err());
+    assert!(AccountAccessor::from_str("cache").is_err());
+
+    let mut new = old.clone();
+    new.user_name = "david".to_string();
+    new.cache.clear();
+    new.members[2].role = "ops".to_string();
+    new.members.swap(0, 1);
+
+    // The skipped cache isn't diffed, and members are matched up by their keys
+    let patch = old.diff(&new).unwrap();
+    let serialized = serde_json::to_value(&patch).unwrap();
+    let actions = serialized["actions"].as_object().unwrap();
+    assert_eq!(actions.keys().collect::<Vec<_>>(), ["members", "userName"]);
+    assert_eq!(actions["members"][0], json!(["List.Move", [1, 0]]));
+    assert_eq!(actions["members"][1][0], json!("List.Update"));
+
+    let patch: Patch = serde_json::from_value(serialized).unwrap();
+    let mut target = old.clone();
+    let undo = target.apply(patch).unwrap();
+    let expected = Account {
+      cache: old.cache.clone(),
+      ..new.clone()
+    };
+    assert_eq!(target, expected);
+    target.apply(undo).unwrap();
+    assert_eq!(target, old);
+  }
+);
+
+test_fn!(
+  fn derived_rename() {
+    use protean::hash::content_hash;
+
+    let old = account();
+    let patch = |expected: &str| {
+      let mut patch = Account::new_patch();
+      patch
+        .add(
+          Action::Set,
+          OwnedValue::new("userName".to_string(), json!("david")),
+          Some(content_hash(&json!(expected))),
+        )
+        .unwrap();
+      patch
+    };
+
+    // Serde still calls the field user_name, but the patch only ever uses userName
+    let inverse = patch("dave").invert(&old).unwrap();
+    let mut target = old.clone();
+    target.apply(patch("dave")).unwrap();
+    assert_eq!(target.user_name, "david");
+    target.apply(inverse).unwrap();
+    assert_eq!(target, old);
+
+    let result = target.apply(patch("someone else"));
+    assert!(
+      matches!(result, Err(ProteanError::ExpectedMismatch { path, .. }) if path == "userName")
+    );
+    assert_eq!(target, old);
+  }
+);
+
+test_fn!(
+  fn derived_immutable() {
+    let old = account();
+    let mut new = old.clone();
+    new.id = 8;
+    new.user_name = "david".to_string();
+
+    // The change is still reported, but applying it fails and leaves the other fields untouched
+    let patch = old.diff(&new).unwrap().into_owned().unwrap();
+    assert!(patch.get("id").is_some());
+
+    let mut target = old.clone();
+    let result = target.apply(patch);
+    assert!(matches!(result, Err(ProteanError::ImmutableField(field)) if field == "id"));
+    assert_eq!(target, old);
+
+    // Replacing the whole struct can't change the field either
+    let set = |account: &Account| {
+      let mut patch = Team::new_patch();
+      patch
+        .add(
+          Action::Set,
+          OwnedValue::new("owner".to_string(), serde_json::to_value(account).unwrap()),
+          None,
+        )
+        .unwrap();
+      patch
+    };
+    let team = Team { owner: old.clone() };
+    let mut target = team.clone();
+    let result = target.apply(set(&new));
+    assert!(matches!(result, Err(ProteanError::ImmutableField(field)) if field == "id"));
+    assert_eq!(target, team);
+
+    let renamed = Account {
+      id: old.id,
+      ..new.clone()
+    };
+    target.apply(set(&renamed)).unwrap();
+    assert_eq!(target.owner, renamed);
+  }
+);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Patchwork)]
+pub struct Team {
+  owner: Account,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Patchwork)]
+pub struct Profile {
+  #[serde(rename = "displayName")]
+  display_name: String,
+  age: u8,
+}
+
+test_fn!(
+  fn derived_expected() {
+    use protean::hash::content_hash;
+
+    let old = Profile {
+      display_name: "dave".to_string(),
+      age: 40,
+    };
+    let patch = |expected: &str| {
+      let mut patch = Profile::new_patch();
+      patch
+        .add(
+          Action::Set,
+          OwnedValue::new("display_name".to_string(), json!("david")),
+          Some(content_hash(&json!(expected))),
+        )
+        .unwrap();
+      patch
+    };
+
+    // The expected hash is checked against the field itself, not whatever serde calls it
+    let mut target = old.clone();
+    target.apply(patch("dave")).unwrap();
+    assert_eq!(target.display_name, "david");
+
+    let mut target = old.clone();
+    let result = target.apply(patch("someone else"));
+    assert!(
+      matches!(result, Err(ProteanError::ExpectedMismatch { path, .. }) if path == "display_name")
+    );
+    assert_eq!(target, old);
+  }
+);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Patchwork)]
+pub struct Empty {}
+
+test_fn!(
+  fn derived_empty() {
+    use std::str::FromStr;
+
+    // A struct without fields never changes, and has nothing a patch can name
+    let empty = Empty {};
+    assert!(empty.diff(&Empty {}).unwrap().is_empty());
+    assert!(empty.values().unwrap().is_empty());
+    assert!(EmptyAccessor::from_str("id").is_err());
+
+    let mut target = empty.clone();
+    target.apply(Empty::new_patch()).unwrap();
+    let mut patch = Empty::new_patch();
+    patch
+      .add(
+        Action::Set,
+        OwnedValue::new("id".to_string(), json!(1)),
+        None,
+      )
+      .unwrap();
+    assert!(target.apply(patch).is_err());
+    assert_eq!(target, empty);
+  }
+);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Patchwork)]
+pub struct Hidden {
+  #[protean(skip)]
+  hidden: u8,
+}
+
+test_fn!(
+  fn derived_skip() {
+    // Every field being skipped leaves nothing to diff
+    let hidden = Hidden { hidden: 1 };
+    assert!(hidden.diff(&Hidden { hidden: 2 }).unwrap().is_empty());
+    assert!(hidden.values().unwrap().is_empty());
+
+    // Replacing the whole struct keeps what the skipped fields were
+    let old = account();
+    let new = Account {
+      user_name: "david".to_string(),
+      cache: vec![9],
+      ..old.clone()
+    };
+    let mut patch = Team::new_patch();
+    patch
+      .add(
+        Action::Set,
+        OwnedValue::new("owner".to_string(), serde_json::to_value(&new).unwrap()),
+        None,
+      )
+      .unwrap();
+
+    let team = Team { owner: old.clone() };
+    let mut target = team.clone();
+    let undo = target.apply(patch).unwrap();
+    assert_eq!(target.owner.user_name, "david");
+    assert_eq!(target.owner.cache, old.cache);
+    target.apply(undo).unwrap();
+    assert_eq!(target, team);
+  }
+);
@@ -0,0 +1,48 @@
+//! The stable hash used for expected values
+
+mod common;
+
+use common::test_fn;
+
+test_fn!(
+  fn hash_is_stable() {
+    use protean::hash::{content_hash, fnv1a};
+    use serde_json::json;
+
+    // Published FNV-1a test vectors
+    assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+    assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+
+    // Fixed so any change to the scheme shows up here instead of between two machines
+    let value = json!({ "b": [1, 2.5, "three"], "a": { "d": null, "c": true } });
+    assert_eq!(
+      content_hash(&value),
+      fnv1a(br#"{"a":{"c":true,"d":null},"b":[1,2.5,"three"]}"#)
+    );
+    assert_eq!(content_hash(&value), 0x3676ecf562ab644e);
+  }
+);
+
+test_fn!(
+  fn hash_uses_content() {
+    use crate::common::{database::*, tester::*};
+    use protean::{hash::hash_serialize, prelude::*};
+
+    // Organization only hashes its id with std::hash, but the content hash sees the name too
+    let org = Organization::new("Before".to_string());
+    let mut renamed = org.clone();
+    renamed.name = "After".to_string();
+    assert_ne!(
+      hash_serialize(&org).unwrap(),
+      hash_serialize(&renamed).unwrap()
+    );
+
+    // A field hashes the same as its value, which is what apply checks against
+    let tester = Tester::random();
+    let field = tester.get_field(TesterAccessor::String).unwrap();
+    assert_eq!(
+      field.content_hash().unwrap(),
+      hash_serialize(&tester.string).unwrap()
+    );
+  }
+);
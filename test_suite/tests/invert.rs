@@ -0,0 +1,155 @@
+//! Undoing a patch with its inverse
+
+mod common;
+
+use common::test_fn;
+
+test_fn!(
+  fn invert_json_actions() {
+    use protean::{impls::json, prelude::*};
+    use serde_json::{json, Value};
+
+    let base = json!({
+      "number": 5,
+      "text": "Text",
+      "list": [1, 2, 3],
+      "map": { "a": 1, "b": { "c": "d" } },
+      "nested": { "e": 6 },
+    });
+
+    let value = |name: &str, val: Value| OwnedValue::new(name.to_string(), val);
+    let list = |act| PatchAction::new(Action::List(act), value("list", json!(4)), None);
+    let map = |act| PatchAction::new(Action::Map(act), value("map", json!(7)), None);
+    let mut nested = Patch::new("map".to_string());
+    nested
+      .add(Action::Set, value("c", json!("changed")), None)
+      .unwrap();
+
+    let actions = vec![
+      PatchAction::new(Action::Set, value("number", json!(10)), None),
+      PatchAction::new(Action::Clear, value("text", Value::Null), None),
+      PatchAction::new(Action::Clear, value("map", Value::Null), None),
+      list(ListAction::Swap(0, 2)),
+      list(ListAction::Remove(1)),
+      list(ListAction::Insert(0)),
+      list(ListAction::Append()),
+      map(MapAction::Insert("z".to_string())),
+      map(MapAction::Update("a".to_string())),
+      map(MapAction::Delete("b".to_string())),
+      PatchAction::from_value(
+        Action::Map(MapAction::Update("b".to_string())),
+        PatchValue::Patch(nested),
+        None,
+      ),
+      PatchAction::from_patch("nested".to_string(), {
+        let mut patch = Patch::new("nested".to_string());
+        patch.add(Action::Set, value("e", json!(8)), None).unwrap();
+        patch
+      }),
+    ];
+
+    for action in actions {
+      let label = format!("{:?}", action.get_action());
+      let mut patch = Patch::new("Root".to_string());
+      patch.add_action(action).unwrap();
+
+      let inverse = json::invert_patch(&base, &patch).unwrap();
+      let mut target = base.clone();
+      json::apply_patch(&mut target, patch).unwrap();
+      assert_ne!(target, base, "{} made no change", label);
+      json::apply_patch(&mut target, inverse).unwrap();
+      assert_eq!(target, base, "{} was not undone", label);
+    }
+  }
+);
+
+test_fn!(
+  fn invert_reset() {
+    use crate::common::tester::*;
+    use protean::prelude::*;
+
+    let base = Tester::random();
+    let mut patch = Patch::new(Tester::get_name());
+    for field in &["integer", "string"] {
+      patch
+        .add(
+          Action::Reset,
+          OwnedValue::new(field.to_string(), serde_json::Value::Null),
+          None,
+        )
+        .unwrap();
+    }
+
+    let inverse = patch.invert(&base).unwrap();
+    let mut target = base.clone();
+    target.apply(patch).unwrap();
+    assert_eq!(target.integer, 0);
+    assert_eq!(target.string, "");
+    target.apply(inverse).unwrap();
+    assert_eq!(target, base);
+  }
+);
+
+test_fn!(
+  fn invert_missing_index() {
+    use protean::{impls::json, prelude::*};
+    use serde_json::json;
+
+    let mut patch = Patch::new("Root".to_string());
+    patch
+      .add(
+        Action::List(ListAction::Remove(3)),
+        OwnedValue::new("list".to_string(), serde_json::Value::Null),
+        None,
+      )
+      .unwrap();
+
+    match json::invert_patch(&json!({ "list": [1, 2, 3] }), &patch) {
+      Err(ProteanError::IndexOutOfRange(3)) => (),
+      result => panic!("Expected an index error, got {:?}", result),
+    }
+  }
+);
+
+test_fn!(
+  fn invert_renamed_field() {
+    use protean::prelude::*;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Patchwork)]
+    struct Profile {
+      #[serde(rename = "displayName")]
+      display_name: String,
+      tags: Vec<String>,
+    }
+
+    let base = Profile {
+      display_name: "dave".to_string(),
+      tags: vec!["a".to_string()],
+    };
+    let mut patch = Profile::new_patch();
+    patch
+      .add(
+        Action::Set,
+        OwnedValue::new("display_name".to_string(), json!("david")),
+        None,
+      )
+      .unwrap();
+    patch
+      .add(
+        Action::List(ListAction::Append()),
+        OwnedValue::new("tags".to_string(), json!("b")),
+        None,
+      )
+      .unwrap();
+
+    // The field is found by its name in the patch, even though serde calls it something else
+    let inverse = patch.invert(&base).unwrap();
+    let mut target = base.clone();
+    target.apply(patch).unwrap();
+    assert_eq!(target.display_name, "david");
+    target.apply(inverse).unwrap();
+    assert_eq!(target, base);
+  }
+);
@@ -0,0 +1,129 @@
+//! Diffing and patching JSON that has no Rust type
+
+mod common;
+
+use common::test_fn;
+
+use rand::prelude::*;
+use serde_json::{json, Value};
+
+/// A small random JSON value, with few enough keys and scalars that two of them often overlap
+fn random_json(rng: &mut ThreadRng, depth: usize) -> Value {
+  match rng.gen_range(0..if depth == 0 { 4 } else { 6 }) {
+    0 => Value::Null,
+    1 => json!(rng.gen_bool(0.5)),
+    2 => json!(rng.gen_range(0..3)),
+    3 => json!(["a", "b", "c"][rng.gen_range(0..3)]),
+    4 => (0..rng.gen_range(0..5))
+      .map(|_| random_json(rng, depth - 1))
+      .collect(),
+    _ => {
+      let mut obj = serde_json::Map::new();
+      for key in ["a", "b", "c", "d"] {
+        if rng.gen_bool(0.6) {
+          obj.insert(key.to_string(), random_json(rng, depth - 1));
+        }
+      }
+      Value::Object(obj)
+    }
+  }
+}
+
+test_fn!(
+  fn diff_json_random() {
+    use protean::{impls::json, prelude::*};
+
+    let mut rng = rand::thread_rng();
+    for _ in 0..500 {
+      let old = random_json(&mut rng, 3);
+      let new = random_json(&mut rng, 3);
+
+      let action = match json::diff("doc", &old, &new).unwrap() {
+        Some(action) => action,
+        None => {
+          assert_eq!(old, new);
+          continue;
+        }
+      };
+
+      // Round trip through the wire format before applying
+      let patch: Patch = serde_json::from_value(json!({
+        "name": "Doc",
+        "version": null,
+        "options": null,
+        "actions": { "doc": serde_json::to_value(&action).unwrap() }
+      }))
+      .unwrap();
+      let mut target = old.clone();
+      let undo = json::apply_action(&mut target, patch.into_actions().next().unwrap()).unwrap();
+      assert_eq!(target, new);
+      json::apply_action(&mut target, undo).unwrap();
+      assert_eq!(target, old);
+    }
+  }
+);
+
+test_fn!(
+  fn diff_json_objects() {
+    use protean::{impls::json, prelude::*};
+
+    let old =
+      json!({ "id": 1, "gone": true, "tags": ["a", "b", "c", "d"], "inner": { "x": 1, "y": 2 } });
+    let new =
+      json!({ "id": 1, "tags": ["b", "c", "d"], "inner": { "x": 1, "y": 3 }, "added": null });
+
+    let action = json::diff("doc", &old, &new).unwrap().unwrap();
+    assert_eq!(
+      serde_json::to_value(&action).unwrap(),
+      json!([
+        ["Map.Delete", "gone"],
+        ["Set", { "Patch": {
+          "name": "doc",
+          "version": null,
+          "options": null,
+          "actions": {
+            "inner": ["Set", { "Patch": {
+              "name": "inner",
+              "version": null,
+              "options": null,
+              "actions": { "y": ["Set", { "Value": 3 }] }
+            }}],
+            "tags": ["List.Remove", 0]
+          }
+        }}],
+        ["Map.Insert", ["added", null]]
+      ])
+    );
+
+    let mut target = old.clone();
+    target.apply_value(action.try_clone().unwrap()).unwrap();
+    assert_eq!(target, new);
+    assert!(json::diff("doc", &old, &old.clone()).unwrap().is_none());
+  }
+);
+
+test_fn!(
+  fn diff_json_matches_typed() {
+    use common::tester::Tester;
+    use protean::{impls::json, prelude::*};
+
+    let old = Tester::default();
+    let mut new = old.clone();
+    new.integer += 1;
+    new.string = "changed".to_string();
+    new.list.push(7);
+    new.nested.level_2 += 1;
+
+    // An object with the same keys gives the same patch as the struct it was serialized from
+    let typed = serde_json::to_value(old.diff(&new).unwrap()).unwrap();
+    let untyped = json::diff(
+      "Tester",
+      &serde_json::to_value(&old).unwrap(),
+      &serde_json::to_value(&new).unwrap(),
+    )
+    .unwrap()
+    .unwrap();
+    let untyped = serde_json::to_value(untyped).unwrap();
+    assert_eq!(untyped[1]["Patch"]["actions"], typed["actions"]);
+  }
+);
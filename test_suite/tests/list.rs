@@ -0,0 +1,174 @@
+//! Diffing lists into the inserts and removes that change them
+
+mod common;
+
+use common::test_fn;
+
+test_fn!(
+  fn diff_list_random() {
+    use protean::impls::list::{apply_list, diff_list};
+    use rand::prelude::*;
+
+    let mut rng = rand::thread_rng();
+    for _ in 0..200 {
+      let old: Vec<u8> = (0..rng.gen_range(0..20))
+        .map(|_| rng.gen_range(0..5))
+        .collect();
+      let new: Vec<u8> = (0..rng.gen_range(0..20))
+        .map(|_| rng.gen_range(0..5))
+        .collect();
+
+      let mut target = old.clone();
+      match diff_list("list", &old, &new).unwrap() {
+        None => assert_eq!(old, new),
+        Some(action) => {
+          let undo = apply_list(&mut target, action).unwrap();
+          assert_eq!(target, new);
+          apply_list(&mut target, undo).unwrap();
+          assert_eq!(target, old);
+        }
+      }
+    }
+  }
+);
+
+test_fn!(
+  fn diff_list_minimal() {
+    use protean::{impls::list::diff_list, prelude::*};
+    use serde_json::json;
+    use std::collections::VecDeque;
+
+    // Changing one element of a long list only sends that element
+    let old: Vec<u32> = (0..5000).collect();
+    let mut new = old.clone();
+    new[2500] = 1;
+    new.push(5000);
+    let action = diff_list("list", &old, &new).unwrap().unwrap();
+    assert_eq!(
+      serde_json::to_value(&action).unwrap(),
+      json!([
+        ["List.Remove", 2500],
+        ["List.Insert", [2500, 1]],
+        ["List.Append", 5000]
+      ])
+    );
+
+    // A single edit is sent on its own, and equal lists have nothing to send
+    let old: VecDeque<&str> = vec!["a", "b", "c"].into();
+    let new: VecDeque<&str> = vec!["a", "c"].into();
+    let action = diff_list("list", &old, &new).unwrap().unwrap();
+    assert_eq!(action.get_action(), &Action::List(ListAction::Remove(1)));
+    assert!(diff_list("list", &old, &old.clone()).unwrap().is_none());
+
+    // Replacing most of the list sends the whole thing instead
+    let action = diff_list("list", &vec![1, 2, 3, 4], &vec![5, 6, 7, 4])
+      .unwrap()
+      .unwrap();
+    assert_eq!(
+      serde_json::to_value(&action).unwrap(),
+      json!(["Set", { "Value": [5, 6, 7, 4] }])
+    );
+  }
+);
+
+test_fn!(
+  fn diff_keyed_records() {
+    use crate::common::tester::*;
+    use protean::{
+      impls::list::{apply_list, diff_keyed},
+      prelude::*,
+    };
+    use serde_json::json;
+
+    let old: Vec<Tester> = (0..6).map(|_| Tester::random()).collect();
+
+    // Reordering only moves the records around, without sending any of them
+    let first = old[..3].to_vec();
+    let reordered = vec![old[2].clone(), old[0].clone(), old[1].clone()];
+    let action = diff_keyed("records", &first, &reordered).unwrap().unwrap();
+    assert_eq!(
+      serde_json::to_value(&action).unwrap(),
+      json!(["List.Move", [2, 0]])
+    );
+
+    // Records are matched by id, so a changed one only sends the fields that changed
+    let mut new = vec![
+      old[4].clone(),
+      old[1].clone(),
+      Tester::random(),
+      old[3].clone(),
+    ];
+    new[1].integer = new[1].integer.wrapping_add(1);
+    let action = diff_keyed("records", &old, &new).unwrap().unwrap();
+    let serialized = serde_json::to_value(&action).unwrap();
+    let update = serialized
+      .as_array()
+      .unwrap()
+      .iter()
+      .find(|step| step[0] == "List.Update")
+      .unwrap();
+    assert_eq!(
+      update[1][1]["Patch"]["actions"],
+      json!({ "integer": ["Set", { "Value": new[1].integer }] })
+    );
+
+    // The steps survive being sent, and can be applied and undone
+    let patch: Patch = serde_json::from_value(json!({
+      "name": "Records",
+      "version": null,
+      "options": null,
+      "actions": { "records": serialized }
+    }))
+    .unwrap();
+    let mut target = old.clone();
+    let undo = apply_list(&mut target, patch.into_actions().next().unwrap()).unwrap();
+    assert_eq!(target, new);
+    apply_list(&mut target, undo).unwrap();
+    assert_eq!(target, old);
+    let copy = old.clone();
+    assert!(diff_keyed("records", &old, &copy).unwrap().is_none());
+  }
+);
+
+test_fn!(
+  fn diff_list_moves() {
+    use protean::impls::list::{apply_list, diff_list};
+    use rand::prelude::*;
+    use serde_json::json;
+
+    // Bumping an item to the front of a queue is a single move
+    let queue: Vec<u32> = (1..=10).collect();
+    let mut bumped = queue.clone();
+    let item = bumped.remove(7);
+    bumped.insert(0, item);
+    let action = diff_list("queue", &queue, &bumped).unwrap().unwrap();
+    assert_eq!(
+      serde_json::to_value(&action).unwrap(),
+      json!(["List.Move", [7, 0]])
+    );
+
+    // Reordered elements are never sent again, whichever way they move
+    let mut rng = rand::thread_rng();
+    for _ in 0..100 {
+      let old: Vec<u32> = (0..30).collect();
+      let mut new = old.clone();
+      for _ in 0..rng.gen_range(1..4) {
+        let item = new.remove(rng.gen_range(0..30));
+        new.insert(rng.gen_range(0..30), item);
+      }
+
+      let mut target = old.clone();
+      let action = match diff_list("queue", &old, &new).unwrap() {
+        Some(action) => action,
+        None => continue,
+      };
+      let serialized = serde_json::to_value(&action).unwrap().to_string();
+      assert!(!serialized.contains("Insert"), "{}", serialized);
+      assert!(!serialized.contains("Remove"), "{}", serialized);
+      let undo = apply_list(&mut target, action).unwrap();
+      assert_eq!(target, new);
+      apply_list(&mut target, undo).unwrap();
+      assert_eq!(target, old);
+    }
+  }
+);
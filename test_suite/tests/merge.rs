@@ -0,0 +1,369 @@
+//! Combining patches that were made against the same model
+
+mod common;
+
+use common::test_fn;
+
+test_fn!(
+  fn merge_disjoint() {
+    use crate::common::tester::*;
+    use protean::prelude::*;
+
+    let base = Tester::random();
+    let mut left = base.clone();
+    left.integer = base.integer.wrapping_add(1);
+    left.nested.level_2 = base.nested.level_2.wrapping_add(1);
+    let mut right = base.clone();
+    right.string = "Right".to_string();
+    right.nested.pk = uuid::Uuid::new_v4();
+
+    let mut patch = base.diff(&left).unwrap();
+    patch.merge(base.diff(&right).unwrap()).unwrap();
+
+    // Both sides end up in the result, including both halves of the nested patch
+    let mut target = base.clone();
+    target.apply(patch).unwrap();
+    assert_eq!(target.integer, left.integer);
+    assert_eq!(target.string, right.string);
+    assert_eq!(target.nested.level_2, left.nested.level_2);
+    assert_eq!(target.nested.pk, right.nested.pk);
+  }
+);
+
+test_fn!(
+  fn merge_conflict() {
+    use crate::common::tester::*;
+    use protean::prelude::*;
+
+    let base = Tester::random();
+    let mut left = base.clone();
+    left.integer = 1;
+    left.string = "Same".to_string();
+    left.nested.level_2 = base.nested.level_2.wrapping_add(1);
+    let mut right = base.clone();
+    right.integer = 2;
+    right.string = "Same".to_string();
+    right.nested.level_2 = base.nested.level_2.wrapping_add(2);
+
+    let mut patch = base.diff(&left).unwrap();
+    let conflicts = match patch.merge(base.diff(&right).unwrap()) {
+      Err(ProteanError::MergeConflict(conflicts)) => conflicts,
+      result => panic!("Expected a merge conflict, got {:?}", result),
+    };
+
+    // Setting the string to the same value is not a conflict
+    let paths: Vec<&str> = conflicts.iter().map(|c| c.path.as_str()).collect();
+    assert_eq!(paths, vec!["integer", "nested.level_2"]);
+    assert_eq!(
+      conflicts[0].ours,
+      serde_json::json!(["Set", { "Value": 1 }])
+    );
+    assert_eq!(
+      conflicts[0].theirs,
+      serde_json::json!(["Set", { "Value": 2 }])
+    );
+
+    // The original patch is left alone
+    assert_eq!(patch, base.diff(&left).unwrap());
+  }
+);
+
+test_fn!(
+  fn compose_sets() {
+    use crate::common::tester::*;
+    use protean::prelude::*;
+
+    let step0 = Tester::random();
+    let mut step1 = step0.clone();
+    step1.integer = 1;
+    step1.nested.level_2 = 1;
+    let mut step2 = step1.clone();
+    step2.integer = 2;
+    step2.string = "Step 2".to_string();
+    step2.nested.pk = uuid::Uuid::new_v4();
+
+    let composed =
+      Patch::compose(step0.diff(&step1).unwrap(), step1.diff(&step2).unwrap()).unwrap();
+
+    // The last set wins, and the nested patch picks up changes from both steps
+    assert_eq!(
+      serde_json::to_value(composed.get("integer").unwrap()).unwrap(),
+      serde_json::json!(["Set", { "Value": 2 }])
+    );
+    let nested = composed.get("nested").unwrap().get_value();
+    match nested {
+      PatchValue::Patch(patch) => assert_eq!(patch.get_actions().count(), 2),
+      _ => panic!("Nested field should still be a patch"),
+    }
+
+    let mut target = step0.clone();
+    target.apply(composed).unwrap();
+    assert_eq!(
+      serde_json::to_value(&target).unwrap(),
+      serde_json::to_value(&step2).unwrap()
+    );
+  }
+);
+
+test_fn!(
+  fn compose_cancels() {
+    use protean::prelude::*;
+
+    let step = |action: Action, value: serde_json::Value| {
+      let mut patch = Patch::new("Db".to_string());
+      patch
+        .add(action, OwnedValue::new("list".to_string(), value), None)
+        .unwrap();
+      patch
+    };
+
+    // Inserting into a list and then removing the same index leaves nothing to do
+    let composed = Patch::compose(
+      step(
+        Action::List(ListAction::Insert(2)),
+        serde_json::json!("Temp"),
+      ),
+      step(Action::List(ListAction::Remove(2)), serde_json::Value::Null),
+    )
+    .unwrap();
+    assert!(composed.is_empty());
+
+    // Two inserts can't be squashed into a single action, so they are kept as steps
+    let composed = Patch::compose(
+      step(
+        Action::List(ListAction::Insert(2)),
+        serde_json::json!("First"),
+      ),
+      step(
+        Action::List(ListAction::Insert(5)),
+        serde_json::json!("Second"),
+      ),
+    )
+    .unwrap();
+    assert_eq!(
+      serde_json::to_value(composed.get("list").unwrap()).unwrap(),
+      serde_json::json!([
+        ["List.Insert", [2, "First"]],
+        ["List.Insert", [5, "Second"]]
+      ])
+    );
+  }
+);
+
+test_fn!(
+  fn compose_replaced_key() {
+    use protean::{impls::json, prelude::*};
+    use serde_json::json;
+
+    let step = |action: MapAction, value: serde_json::Value| {
+      let mut patch = Patch::new("Db".to_string());
+      patch
+        .add(
+          Action::Map(action),
+          OwnedValue::new("map".to_string(), value),
+          None,
+        )
+        .unwrap();
+      patch
+    };
+    let insert = || step(MapAction::Insert("k".to_string()), json!(2));
+    let delete = || step(MapAction::Delete("k".to_string()), serde_json::Value::Null);
+
+    // The insert may overwrite a key that was already there, so deleting it afterwards is not a no-op
+    for start in [json!({ "map": { "k": 1 } }), json!({ "map": {} })] {
+      let mut sequential = start.clone();
+      json::apply_patch(&mut sequential, insert()).unwrap();
+      json::apply_patch(&mut sequential, delete()).unwrap();
+
+      let mut composed = start.clone();
+      json::apply_patch(&mut composed, Patch::compose(insert(), delete()).unwrap()).unwrap();
+      assert_eq!(composed, sequential);
+      assert_eq!(composed, json!({ "map": {} }));
+    }
+  }
+);
+
+test_fn!(
+  fn merge3_maps() {
+    use crate::common::database::*;
+    use protean::prelude::*;
+
+    let mut base = Db::new();
+    let first = Organization::new("First".to_string());
+    let second = Organization::new("Second".to_string());
+    base.organizations.insert(first.org_id, first.clone());
+    base.organizations.insert(second.org_id, second.clone());
+
+    // Each side edits a different key of the same map, and adds its own
+    let mut ours = base.clone();
+    ours.organizations.get_mut(&first.org_id).unwrap().name = "Ours".to_string();
+    let added = Organization::new("Added".to_string());
+    ours.organizations.insert(added.org_id, added.clone());
+    let mut theirs = base.clone();
+    theirs.organizations.remove(&second.org_id);
+    let address = Address::new("Theirs".to_string());
+    theirs.addresses.insert(address.addr_id, address.clone());
+
+    let (patch, conflicts) = Patch::merge3(&base, &ours, &theirs).unwrap();
+    assert!(conflicts.is_empty(), "{:?}", conflicts);
+
+    // Only the keys that changed are sent, not the whole map
+    let organizations = serde_json::to_value(patch.get("organizations").unwrap()).unwrap();
+    let steps: Vec<&serde_json::Value> = organizations
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|step| &step[0])
+      .collect();
+    assert_eq!(steps, ["Map.Delete", "Set", "Map.Insert"]);
+    assert_eq!(organizations[0][1], serde_json::json!(second.org_id));
+
+    let mut target = base.clone();
+    target.apply(patch).unwrap();
+    assert_eq!(target.organizations.len(), 2);
+    assert_eq!(target.organizations[&first.org_id].name, "Ours");
+    assert!(target.organizations.contains_key(&added.org_id));
+    assert!(target.addresses.contains_key(&address.addr_id));
+  }
+);
+
+test_fn!(
+  fn merge3_lists() {
+    use crate::common::tester::*;
+    use protean::prelude::*;
+
+    let mut base = Tester::random();
+    base.list = (1..=10).collect();
+
+    // Ours changes the front of the list and theirs the back, along with different nested fields
+    let mut ours = base.clone();
+    ours.list = vec![0, 1, 3, 4, 5, 6, 7, 8, 9, 10];
+    ours.nested.level_2 = base.nested.level_2.wrapping_add(1);
+    let mut theirs = base.clone();
+    theirs.list = (1..=12).collect();
+    theirs.nested.pk = uuid::Uuid::new_v4();
+
+    let (patch, conflicts) = Patch::merge3(&base, &ours, &theirs).unwrap();
+    assert!(conflicts.is_empty(), "{:?}", conflicts);
+
+    // The list is patched in place rather than being sent again
+    let list = serde_json::to_value(patch.get("list").unwrap()).unwrap();
+    for step in list.as_array().unwrap() {
+      assert!(step[0].as_str().unwrap().starts_with("List."), "{}", step);
+    }
+
+    let mut target = base.clone();
+    target.apply(patch).unwrap();
+    assert_eq!(target.list, vec![0, 1, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+    assert_eq!(target.nested.level_2, ours.nested.level_2);
+    assert_eq!(target.nested.pk, theirs.nested.pk);
+  }
+);
+
+test_fn!(
+  fn merge3_sets() {
+    use protean::prelude::*;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeSet;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Patchwork)]
+    struct Tagged {
+      tags: BTreeSet<String>,
+    }
+
+    let tagged = |tags: &[&str]| Tagged {
+      tags: tags.iter().map(|tag| tag.to_string()).collect(),
+    };
+    let base = tagged(&["a", "b", "c"]);
+    let ours = tagged(&["b", "c"]);
+    let theirs = tagged(&["a", "b"]);
+
+    // Each side removed a different member, which is sent as set actions rather than the whole set
+    let (patch, conflicts) = Patch::merge3(&base, &ours, &theirs).unwrap();
+    assert!(conflicts.is_empty(), "{:?}", conflicts);
+    assert_eq!(
+      serde_json::to_value(patch.get("tags").unwrap()).unwrap(),
+      serde_json::json!([["Set.Remove", "a"], ["Set.Remove", "c"]])
+    );
+
+    let mut target = base.clone();
+    target.apply(patch).unwrap();
+    assert_eq!(target, tagged(&["b"]));
+  }
+);
+
+test_fn!(
+  fn merge3_conflicts() {
+    use crate::common::tester::*;
+    use protean::prelude::*;
+
+    let mut base = Tester::random();
+    base.list = vec![1, 2, 3, 4, 5];
+
+    let mut ours = base.clone();
+    ours.integer = 1;
+    ours.list = vec![1, 2, 10, 4, 5];
+    ours.string = "Same".to_string();
+    let mut theirs = base.clone();
+    theirs.integer = 2;
+    theirs.list = vec![1, 2, 20, 4, 5];
+    theirs.string = "Same".to_string();
+
+    let (patch, conflicts) = Patch::merge3(&base, &ours, &theirs).unwrap();
+    let paths: Vec<&str> = conflicts.iter().map(|c| c.path.as_str()).collect();
+    assert_eq!(paths, vec!["integer", "list.2"]);
+    assert_eq!(
+      conflicts[1].theirs,
+      serde_json::json!(["Set", { "Value": 20 }])
+    );
+
+    // Conflicting fields keep our version
+    let mut target = base.clone();
+    target.apply(patch).unwrap();
+    assert_eq!(target.integer, 1);
+    assert_eq!(target.list, vec![1, 2, 10, 4, 5]);
+    assert_eq!(target.string, "Same");
+  }
+);
+
+test_fn!(
+  fn merge3_keyed_lists() {
+    use protean::prelude::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Patchwork)]
+    struct Records {
+      #[protean(with = "protean::impls::list::keyed")]
+      recs: Vec<Rec>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Patchwork)]
+    struct Rec {
+      #[protean(key)]
+      pk: u32,
+      val: u32,
+    }
+
+    let base = Records {
+      recs: (0..10).map(|pk| Rec { pk, val: 0 }).collect(),
+    };
+
+    // Each side updates a different record, which are both kept as list changes
+    let mut ours = base.clone();
+    ours.recs[7].val = 2;
+    let mut theirs = base.clone();
+    theirs.recs[2].val = 1;
+
+    let (patch, conflicts) = Patch::merge3(&base, &ours, &theirs).unwrap();
+    assert!(conflicts.is_empty(), "{:?}", conflicts);
+    let recs = serde_json::to_value(patch.get("recs").unwrap()).unwrap();
+    for step in recs.as_array().unwrap() {
+      assert!(step[0].as_str().unwrap().starts_with("List."), "{}", step);
+    }
+
+    let mut target = base.clone();
+    target.apply(patch).unwrap();
+    let vals: Vec<u32> = target.recs.iter().map(|rec| rec.val).collect();
+    assert_eq!(vals, vec![0, 0, 1, 0, 0, 0, 0, 2, 0, 0]);
+  }
+);
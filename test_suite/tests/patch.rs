@@ -0,0 +1,353 @@
+//! Serializing patches and sending them between processes
+
+mod common;
+
+use common::test_fn;
+
+test_fn!(
+  fn round_trip() {
+    use crate::common::tester::*;
+    use protean::prelude::*;
+
+    let test1 = Tester::random();
+    let test2 = Tester::random();
+    let patch = test1.diff(&test2).unwrap();
+
+    let serialized = serde_json::to_value(&patch).unwrap();
+    log::debug!("Serialized patch:\n{:#}", serialized);
+    let deserialized: Patch = serde_json::from_value(serialized.clone()).unwrap();
+    assert_eq!(patch, deserialized);
+    assert_eq!(serialized, serde_json::to_value(&deserialized).unwrap());
+
+    // The deserialized patch can be applied just like the original
+    let mut target = test1.clone();
+    target.apply(deserialized).unwrap();
+    assert_eq!(
+      serde_json::to_value(&target).unwrap(),
+      serde_json::to_value(&test2).unwrap()
+    );
+  }
+);
+
+test_fn!(
+  fn round_trip_expected() {
+    use protean::prelude::*;
+
+    let json = serde_json::json!({
+      "name": "Db",
+      "version": null,
+      "options": { "allow_upsert": false, "on_mismatch": "Fail" },
+      "actions": {
+        "addr_map": ["Set", { "Value": { "Key": "Value" } }, 1234],
+      }
+    });
+
+    let patch: Patch = serde_json::from_value(json.clone()).unwrap();
+    let action = patch.get_actions().next().unwrap();
+    assert_eq!(action.get_name(), "addr_map");
+    assert_eq!(action.get_expected(), Some(1234));
+    assert_eq!(serde_json::to_value(&patch).unwrap(), json);
+  }
+);
+
+test_fn!(
+  fn owned_patch() {
+    use crate::common::tester::*;
+    use protean::prelude::*;
+
+    fn assert_sendable<T: Send + Sync + 'static>() {}
+    assert_sendable::<PatchBuf>();
+
+    let test1 = Tester::random();
+    let test2 = Tester::random();
+    let expected = serde_json::to_value(&test2).unwrap();
+
+    // The owned patch no longer needs either tester to stay alive
+    let patch: PatchBuf = test1.diff(&test2).unwrap().into_owned().unwrap();
+    drop(test2);
+
+    let (sender, receiver) = std::sync::mpsc::channel::<PatchBuf>();
+    std::thread::spawn(move || sender.send(patch).unwrap())
+      .join()
+      .unwrap();
+
+    let mut target = test1.clone();
+    let mut history: Vec<PatchBuf> = Vec::new();
+    let reverse = target.apply(receiver.recv().unwrap()).unwrap();
+    history.push(reverse.into_owned().unwrap());
+    assert_eq!(serde_json::to_value(&target).unwrap(), expected);
+
+    target.apply(history.pop().unwrap()).unwrap();
+    assert_eq!(
+      serde_json::to_value(&target).unwrap(),
+      serde_json::to_value(&test1).unwrap()
+    );
+  }
+);
+
+test_fn!(
+  fn reset_and_clear() {
+    use crate::common::tester::*;
+    use protean::prelude::*;
+    use serde_json::json;
+
+    let json = json!({
+      "name": "Tester",
+      "version": null,
+      "options": { "allow_upsert": false, "on_mismatch": "Fail" },
+      "actions": {
+        "integer": ["Reset"],
+        "string": ["Clear"],
+        "list": ["Clear", null, 1234],
+        "nested": ["Reset", null, 5678],
+      }
+    });
+    let patch: Patch = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(patch.get("list").unwrap().get_action(), &Action::Clear);
+    assert_eq!(patch.get("nested").unwrap().get_expected(), Some(5678));
+    assert_eq!(serde_json::to_value(&patch).unwrap(), json);
+
+    // Reset goes to the default and Clear empties the value, and undoing restores both
+    let original = Tester::random();
+    let mut target = original.clone();
+    let mut patch = Tester::new_patch();
+    for (field, action) in [
+      ("integer", Action::Clear),
+      ("string", Action::Clear),
+      ("list", Action::Clear),
+      ("nested", Action::Reset),
+    ] {
+      patch
+        .add(
+          action,
+          OwnedValue::new(field.to_string(), serde_json::Value::Null),
+          None,
+        )
+        .unwrap();
+    }
+    let undo = target.apply(patch).unwrap();
+    assert_eq!(target.integer, 0);
+    assert_eq!(target.string, "");
+    assert!(target.list.is_empty());
+    assert_eq!(target.nested.level_2, 0);
+    target.apply(undo).unwrap();
+    assert_eq!(target, original);
+
+    // A Uuid has no empty value, so clearing it is an error rather than a panic
+    let mut patch = Tester::new_patch();
+    patch
+      .add(
+        Action::Clear,
+        OwnedValue::new("nested".to_string(), serde_json::Value::Null),
+        None,
+      )
+      .unwrap();
+    assert!(target.apply(patch).is_err());
+    assert_eq!(target, original);
+
+    // Booleans clear to false, and null is already empty
+    let mut value = json!({ "flag": true, "missing": null, "count": 3 });
+    protean::impls::json::clear(&mut value).unwrap();
+    assert_eq!(value, json!({ "flag": false, "missing": null, "count": 0 }));
+  }
+);
+
+test_fn!(
+  fn list_actions() {
+    use crate::common::tester::*;
+    use protean::{hash::content_hash, impls::list::apply_list, prelude::*};
+    use serde_json::json;
+    use std::collections::VecDeque;
+
+    let action = |json: serde_json::Value| {
+      let patch: Patch = serde_json::from_value(json!({
+        "name": "Tester",
+        "version": null,
+        "options": null,
+        "actions": { "list": json.clone() }
+      }))
+      .unwrap();
+      assert_eq!(
+        serde_json::to_value(patch.get("list").unwrap()).unwrap(),
+        json
+      );
+      patch
+    };
+
+    let mut target = Tester::random();
+    target.list = vec![1, 2, 3];
+    let original = target.clone();
+    let mut undo = Vec::new();
+    for json in [
+      json!(["List.Swap", [0, 2]]),
+      json!(["List.Remove", 1]),
+      json!(["List.Insert", [1, 10], content_hash(&json!([3, 1]))]),
+      json!(["List.Append", 20]),
+      json!(["List.Move", [0, 3]]),
+    ] {
+      undo.push(target.apply(action(json)).unwrap());
+    }
+    assert_eq!(target.list, vec![10, 1, 20, 3]);
+    for patch in undo.into_iter().rev() {
+      target.apply(patch).unwrap();
+    }
+    assert_eq!(target, original);
+
+    // The same actions work on a VecDeque, and report bad indices instead of panicking
+    let mut deque: VecDeque<String> = vec!["a".to_string(), "b".to_string()].into();
+    let append = action(json!(["List.Append", "c"]))
+      .into_actions()
+      .next()
+      .unwrap();
+    let undo = apply_list(&mut deque, append).unwrap();
+    assert_eq!(deque, vec!["a", "b", "c"]);
+    apply_list(&mut deque, undo).unwrap();
+    assert_eq!(deque, vec!["a", "b"]);
+
+    let remove = action(json!(["List.Remove", 5]))
+      .into_actions()
+      .next()
+      .unwrap();
+    assert!(matches!(
+      apply_list(&mut deque, remove),
+      Err(ProteanError::IndexOutOfRange(5))
+    ));
+  }
+);
+
+test_fn!(
+  fn map_actions() {
+    use crate::common::database::*;
+    use protean::{impls::map::apply_map, prelude::*};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    let patch = |actions: serde_json::Value, allow_upsert: bool| {
+      let patch: Patch = serde_json::from_value(json!({
+        "name": "Db",
+        "version": null,
+        "options": { "allow_upsert": allow_upsert, "on_mismatch": "Fail" },
+        "actions": actions.clone()
+      }))
+      .unwrap();
+      assert_eq!(serde_json::to_value(&patch).unwrap()["actions"], actions);
+      patch
+    };
+
+    let kept = Organization::new("Kept".to_string());
+    let removed = Organization::new("Removed".to_string());
+    let address = Address::new("Line 1".to_string());
+    let mut db = Db::new();
+    db.organizations.insert(kept.org_id, kept.clone());
+    db.organizations.insert(removed.org_id, removed.clone());
+    db.addresses.insert(address.addr_id, address.clone());
+    let original = db.clone();
+
+    let added = Organization::new("Added".to_string());
+    let undo = db
+      .apply(patch(
+        json!({
+          "organizations": ["Map.Insert", [added.org_id, added]],
+          "addresses": ["Map.Update", [address.addr_id, {
+            "Patch": {
+              "name": "addresses",
+              "version": null,
+              "options": null,
+              "actions": { "line1": ["Set", { "Value": "Line 2" }] }
+            }
+          }]],
+        }),
+        true,
+      ))
+      .unwrap();
+    db.apply(patch(
+      json!({ "organizations": ["Map.Delete", removed.org_id] }),
+      true,
+    ))
+    .unwrap();
+    assert_eq!(db.organizations.len(), 2);
+    assert_eq!(db.organizations[&added.org_id].name, "Added");
+    assert!(!db.organizations.contains_key(&removed.org_id));
+    assert_eq!(db.addresses[&address.addr_id].line1, "Line 2");
+
+    // Inserting over an existing key is an update only when upserts are allowed
+    let renamed = Organization {
+      org_id: kept.org_id,
+      name: "Renamed".to_string(),
+    };
+    let insert = json!({ "organizations": ["Map.Insert", [kept.org_id, renamed]] });
+    assert!(matches!(
+      db.apply(patch(insert.clone(), false)),
+      Err(ProteanError::DuplicateKey)
+    ));
+    db.apply(patch(insert, true)).unwrap();
+    assert_eq!(db.organizations[&kept.org_id].name, "Renamed");
+
+    // Undoing the first patch only reverts what it changed
+    db.apply(undo).unwrap();
+    assert_eq!(db.addresses[&address.addr_id].line1, "Line 1");
+    assert!(!db.organizations.contains_key(&added.org_id));
+    assert_eq!(db.organizations.len(), original.organizations.len() - 1);
+
+    // Keys that aren't strings are read back from their serialized form
+    let mut scores: BTreeMap<u32, String> = BTreeMap::new();
+    scores.insert(1, "One".to_string());
+    let delete = patch(json!({ "scores": ["Map.Delete", "1"] }), true);
+    let undo = apply_map(&mut scores, delete.into_actions().next().unwrap()).unwrap();
+    assert!(scores.is_empty());
+    apply_map(&mut scores, undo).unwrap();
+    assert_eq!(scores[&1], "One");
+  }
+);
+
+test_fn!(
+  fn multi_step() {
+    use crate::common::tester::*;
+    use protean::prelude::*;
+    use serde_json::json;
+
+    let json = json!({
+      "name": "Tester",
+      "version": null,
+      "options": null,
+      "actions": {
+        "list": [["List.Append", 4], ["List.Remove", 0], ["List.Swap", [0, 1]]],
+      }
+    });
+    let patch: Patch = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(patch.get("list").unwrap().get_action(), &Action::Steps);
+    assert_eq!(serde_json::to_value(&patch).unwrap(), json);
+
+    // The steps run in order, and undoing them runs the reverse steps backwards
+    let mut target = Tester::random();
+    target.list = vec![1, 2, 3];
+    let original = target.clone();
+    let undo = target.apply(patch).unwrap();
+    assert_eq!(target.list, vec![3, 2, 4]);
+    target.apply(undo).unwrap();
+    assert_eq!(target, original);
+
+    // A later step failing rolls back the ones before it
+    let mut patch = Tester::new_patch();
+    for (action, value) in [
+      (Action::List(ListAction::Append()), json!(5)),
+      (
+        Action::List(ListAction::Remove(10)),
+        serde_json::Value::Null,
+      ),
+    ] {
+      patch
+        .add_step(PatchAction::new(
+          action,
+          OwnedValue::new("list".to_string(), value),
+          None,
+        ))
+        .unwrap();
+    }
+    assert!(matches!(
+      target.apply(patch),
+      Err(ProteanError::IndexOutOfRange(10))
+    ));
+    assert_eq!(target, original);
+  }
+);
@@ -0,0 +1,262 @@
+//! Generating and applying patches between two instances of a Patchwork struct
+
+mod common;
+
+use common::test_fn;
+
+test_fn!(
+  fn diff_self() {
+    use crate::common::tester::*;
+    use protean::prelude::*;
+
+    // When compared to itself, a struct should return an empty patch
+    let test1 = Tester::random();
+    let patch = test1.diff(&test1).unwrap();
+    log::debug!("Self Test:\n{:#?}", patch);
+    assert!(patch.is_empty(), "Patch was not empty");
+  }
+);
+
+test_fn!(
+  fn diff_changed_fields() {
+    use crate::common::tester::*;
+    use protean::prelude::*;
+
+    let test1 = Tester::random();
+    let mut test2 = test1.clone();
+    test2.integer = test1.integer.wrapping_add(1);
+    test2.nested.level_2 = test1.nested.level_2.wrapping_add(1);
+
+    let patch = test1.diff(&test2).unwrap();
+    log::debug!("Changed fields:\n{:#?}", patch);
+
+    // Only the changed fields are included, and nested structs are patched instead of set
+    let serialized = serde_json::to_value(&patch).unwrap();
+    let actions = serialized["actions"].as_object().unwrap();
+    assert_eq!(actions.len(), 2);
+    assert_eq!(
+      actions["integer"],
+      serde_json::json!(["Set", { "Value": test2.integer }])
+    );
+    assert_eq!(actions["nested"][0], "Set");
+    assert_eq!(actions["nested"][1]["Patch"]["name"], "nested");
+    assert_eq!(
+      actions["nested"][1]["Patch"]["actions"]["level_2"],
+      serde_json::json!(["Set", { "Value": test2.nested.level_2 }])
+    );
+  }
+);
+
+test_fn!(
+  fn diff_db() {
+    use crate::common::database::*;
+    use protean::prelude::*;
+
+    let db1 = Db::new();
+    let mut db2 = db1.clone();
+    let org = Organization::new("Protean".to_string());
+    db2.organizations.insert(org.org_id, org);
+
+    let patch = db1.diff(&db2).unwrap();
+    let serialized = serde_json::to_value(&patch).unwrap();
+    let actions = serialized["actions"].as_object().unwrap();
+    assert_eq!(actions.len(), 1);
+    assert!(actions.contains_key("organizations"));
+  }
+);
+
+test_fn!(
+  fn apply_diff() {
+    use crate::common::tester::*;
+    use protean::prelude::*;
+
+    let test1 = Tester::random();
+    let test2 = Tester::random();
+    let patch = test1.diff(&test2).unwrap();
+
+    // Applying the diff turns the first into the second
+    let mut target = test1.clone();
+    let reverse = target.apply(patch).unwrap();
+    assert_eq!(
+      serde_json::to_value(&target).unwrap(),
+      serde_json::to_value(&test2).unwrap()
+    );
+
+    // And the reverse patch puts it back again
+    target.apply(reverse).unwrap();
+    assert_eq!(
+      serde_json::to_value(&target).unwrap(),
+      serde_json::to_value(&test1).unwrap()
+    );
+  }
+);
+
+test_fn!(
+  fn apply_map_action() {
+    use crate::common::database::*;
+    use protean::prelude::*;
+
+    let mut db = Db::new();
+    let org = Organization::new("Protean".to_string());
+
+    let mut patch = Db::new_patch();
+    patch
+      .add(
+        Action::Map(MapAction::Insert(org.org_id.to_string())),
+        OwnedValue::new(
+          "organizations".to_string(),
+          serde_json::to_value(&org).unwrap(),
+        ),
+        None,
+      )
+      .unwrap();
+
+    let reverse = db.apply(patch).unwrap();
+    assert_eq!(db.organizations[&org.org_id].name, "Protean");
+
+    db.apply(reverse).unwrap();
+    assert!(db.organizations.is_empty());
+  }
+);
+
+test_fn!(
+  fn apply_rollback() {
+    use crate::common::tester::*;
+    use protean::prelude::*;
+
+    let original = Tester::random();
+    let mut target = original.clone();
+
+    // One good field and one that can't be deserialized into an i32
+    let mut patch = Tester::new_patch();
+    patch
+      .add(
+        Action::Set,
+        OwnedValue::new("string".to_string(), serde_json::json!("Changed")),
+        None,
+      )
+      .unwrap();
+    patch
+      .add(
+        Action::Set,
+        OwnedValue::new("integer".to_string(), serde_json::json!("Not a number")),
+        None,
+      )
+      .unwrap();
+
+    assert!(target.apply(patch).is_err());
+    assert_eq!(
+      serde_json::to_value(&target).unwrap(),
+      serde_json::to_value(&original).unwrap()
+    );
+  }
+);
+
+test_fn!(
+  fn apply_expected() {
+    use crate::common::tester::*;
+    use protean::{hash::content_hash, prelude::*};
+    use serde_json::json;
+
+    let original = Tester::random();
+    let hash = |value: serde_json::Value| Some(content_hash(&value));
+    let patch = |on_mismatch: OnMismatch, stale: bool| {
+      let integer = match stale {
+        true => original.integer.wrapping_add(1),
+        false => original.integer,
+      };
+      let mut nested = Nested::new_patch();
+      nested
+        .add(
+          Action::Set,
+          OwnedValue::new("level_2".to_string(), json!(7)),
+          hash(json!(original.nested.level_2)),
+        )
+        .unwrap();
+
+      let mut patch = Tester::new_patch();
+      patch
+        .add(
+          Action::Set,
+          OwnedValue::new("integer".to_string(), json!(5)),
+          hash(json!(integer)),
+        )
+        .unwrap();
+      patch
+        .add(
+          Action::Set,
+          OwnedValue::new("string".to_string(), json!("Changed")),
+          None,
+        )
+        .unwrap();
+      patch
+        .add_action(PatchAction::from_patch("nested".to_string(), nested))
+        .unwrap();
+      let mut options = PatchOptions::default();
+      options.set_on_mismatch(on_mismatch);
+      patch.set_options(options);
+      patch
+    };
+
+    // The hashes match, so everything is applied
+    let mut target = original.clone();
+    target.apply(patch(OnMismatch::Fail, false)).unwrap();
+    assert_eq!(target.integer, 5);
+    assert_eq!(target.nested.level_2, 7);
+
+    // Someone else changed the nested field after the patch was made
+    let mut target = original.clone();
+    target.nested.level_2 = 7;
+    target.string = "Other".to_string();
+    let changed = target.clone();
+    match target.apply(patch(OnMismatch::Fail, false)) {
+      Err(ProteanError::ExpectedMismatch {
+        path,
+        expected,
+        found,
+      }) => {
+        assert_eq!(path, "nested.level_2");
+        assert_eq!(Some(expected), hash(json!(original.nested.level_2)));
+        assert_eq!(Some(found), hash(json!(7)));
+      }
+      result => panic!("Expected a mismatch, got {:?}", result),
+    }
+    assert_eq!(target, changed);
+
+    // With more than one stale field, the first by name is always the one reported
+    for _ in 0..10 {
+      let mut target = changed.clone();
+      match target.apply(patch(OnMismatch::Fail, true)) {
+        Err(ProteanError::ExpectedMismatch { path, .. }) => assert_eq!(path, "integer"),
+        result => panic!("Expected a mismatch, got {:?}", result),
+      }
+    }
+
+    // Skipping leaves the stale field alone but applies the rest
+    let mut target = original.clone();
+    target.apply(patch(OnMismatch::Skip, true)).unwrap();
+    assert_eq!(target.integer, original.integer);
+    assert_eq!(target.string, "Changed");
+    assert_eq!(target.nested.level_2, 7);
+
+    // Forcing writes it regardless
+    let mut target = original.clone();
+    target.apply(patch(OnMismatch::Force, true)).unwrap();
+    assert_eq!(target.integer, 5);
+
+    // The nested patch has no options of its own, so it follows the root when its field is stale
+    let mut target = original.clone();
+    target.nested.level_2 = original.nested.level_2.wrapping_add(1);
+    target.apply(patch(OnMismatch::Skip, false)).unwrap();
+    assert_eq!(target.integer, 5);
+    assert_eq!(
+      target.nested.level_2,
+      original.nested.level_2.wrapping_add(1)
+    );
+
+    let mut target = original.clone();
+    target.nested.level_2 = original.nested.level_2.wrapping_add(1);
+    target.apply(patch(OnMismatch::Force, false)).unwrap();
+    assert_eq!(target.nested.level_2, 7);
+  }
+);
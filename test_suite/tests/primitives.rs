@@ -0,0 +1,136 @@
+//! Structs made of plain fields, using Field and FieldName instead of their own enums
+
+mod common;
+
+use common::test_fn;
+
+use protean::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct Plain {
+  id: Uuid,
+  flag: bool,
+  letter: char,
+  small: i8,
+  big: u128,
+  size: usize,
+  ratio: f64,
+  label: String,
+  nickname: Option<String>,
+}
+
+impl<'a> Patchwork<'a> for Plain {
+  type Accessor = FieldName;
+  type Element = Field<'a>;
+
+  fn get_field(&'a self, name: FieldName) -> Result<Field<'a>, ProteanError> {
+    self
+      .values()?
+      .into_iter()
+      .find(|field| field.get_field_name() == name.as_str())
+      .ok_or_else(|| ProteanError::KeyNotFound(name.to_string()))
+  }
+
+  fn values(&'a self) -> Result<Vec<Field<'a>>, ProteanError> {
+    Ok(vec![
+      Field::new("id", &self.id),
+      Field::new("flag", &self.flag),
+      Field::new("letter", &self.letter),
+      Field::new("small", &self.small),
+      Field::new("big", &self.big),
+      Field::new("size", &self.size),
+      Field::new("ratio", &self.ratio),
+      Field::new("label", &self.label),
+      Field::new("nickname", &self.nickname),
+    ])
+  }
+
+  fn apply_field(
+    &mut self,
+    field: FieldName,
+    action: PatchAction,
+  ) -> Result<PatchAction<'a>, ProteanError> {
+    match field.as_str() {
+      "id" => self.id.apply_value(action),
+      "flag" => self.flag.apply_value(action),
+      "letter" => self.letter.apply_value(action),
+      "small" => self.small.apply_value(action),
+      "big" => self.big.apply_value(action),
+      "size" => self.size.apply_value(action),
+      "ratio" => self.ratio.apply_value(action),
+      "label" => self.label.apply_value(action),
+      "nickname" => self.nickname.apply_value(action),
+      _ => Err(ProteanError::KeyNotFound(field.to_string())),
+    }
+  }
+}
+
+fn plain() -> Plain {
+  Plain {
+    id: Uuid::new_v4(),
+    flag: false,
+    letter: 'a',
+    small: -8,
+    big: u128::MAX,
+    size: 3,
+    ratio: 0.5,
+    label: "first".to_string(),
+    nickname: None,
+  }
+}
+
+test_fn!(
+  fn diff_plain_fields() {
+    use serde_json::json;
+
+    let old = plain();
+    let mut new = old.clone();
+    new.flag = true;
+    new.small = 8;
+    new.label = "second".to_string();
+    new.nickname = Some("nick".to_string());
+
+    let patch = old.diff(&new).unwrap();
+    let serialized = serde_json::to_value(&patch).unwrap();
+    assert_eq!(
+      serialized["actions"],
+      json!({
+        "flag": ["Set", { "Value": true }],
+        "small": ["Set", { "Value": 8 }],
+        "label": ["Set", { "Value": "second" }],
+        "nickname": ["Set", { "Value": "nick" }],
+      })
+    );
+    assert!(old.diff(&old.clone()).unwrap().is_empty());
+
+    // Round trip through serde, then apply and undo
+    let patch: Patch = serde_json::from_value(serialized).unwrap();
+    let mut target = old.clone();
+    let undo = target.apply(patch).unwrap();
+    assert_eq!(target, new);
+    target.apply(undo).unwrap();
+    assert_eq!(target, old);
+  }
+);
+
+test_fn!(
+  fn primitive_values() {
+    let (old, other) = (1.5f32, 2.5f32);
+    let mut number = old;
+    let action = old.diff_value("number", &other).unwrap().unwrap();
+    let undo = number.apply_value(action).unwrap();
+    assert_eq!(number, other);
+    number.apply_value(undo).unwrap();
+    assert_eq!(number, old);
+    assert!(old.diff_value("number", &1.5).unwrap().is_none());
+
+    // A borrowed str diffs, but can't be patched in place
+    let (old, other) = ("left", "right");
+    let mut text = old;
+    let action = old.diff_value("text", &other).unwrap().unwrap();
+    assert!(text.apply_value(action).is_err());
+    assert_eq!(text, "left");
+  }
+);
@@ -0,0 +1,294 @@
+//! Moving patches past concurrent edits so both sides converge
+
+mod common;
+
+use common::test_fn;
+
+test_fn!(
+  fn rebase_list_converges() {
+    use protean::{impls::json, prelude::*};
+    use serde_json::{json, Value};
+
+    let base = json!({ "list": [10, 11, 12, 13, 14] });
+    let mut edits = vec![
+      (ListAction::Append(), json!("a")),
+      (ListAction::Append(), json!("b")),
+    ];
+    for idx in 0..5 {
+      edits.push((ListAction::Remove(idx), Value::Null));
+      edits.push((ListAction::Swap(idx, 4 - idx), Value::Null));
+      edits.push((ListAction::Update(idx), json!("u")));
+      edits.push((ListAction::Update(idx), json!("v")));
+      edits.push((ListAction::Move(idx, 4 - idx), Value::Null));
+      edits.push((ListAction::Move(idx, (idx + 2) % 5), Value::Null));
+    }
+    for idx in 0..=5 {
+      edits.push((ListAction::Insert(idx), json!("a")));
+      edits.push((ListAction::Insert(idx), json!("b")));
+    }
+    let patch = |(act, value): &(ListAction, Value)| {
+      let mut patch = Patch::new("Root".to_string());
+      patch
+        .add(
+          Action::List(act.clone()),
+          OwnedValue::new("list".to_string(), value.clone()),
+          None,
+        )
+        .unwrap();
+      patch
+    };
+    let apply = |first: Patch, second: Patch| {
+      let mut target = base.clone();
+      json::apply_patch(&mut target, first).unwrap();
+      json::apply_patch(&mut target, second).unwrap();
+      target
+    };
+
+    let mut converged = 0;
+    for left in &edits {
+      for right in &edits {
+        let rebased_left = patch(left).rebase(&patch(right));
+        let rebased_right = patch(right).rebase(&patch(left));
+        match (rebased_left, rebased_right) {
+          (Ok(rebased_left), Ok(rebased_right)) => {
+            assert_eq!(
+              apply(patch(left), rebased_right),
+              apply(patch(right), rebased_left),
+              "{:?} and {:?} did not converge",
+              left,
+              right
+            );
+            converged += 1;
+          }
+          (Err(ProteanError::RebaseConflict(path)), Err(ProteanError::RebaseConflict(_))) => {
+            assert_eq!(path, "list")
+          }
+          result => panic!(
+            "Only one side of {:?} and {:?} rebased: {:?}",
+            left, right, result
+          ),
+        }
+      }
+    }
+    assert!(converged > edits.len() * edits.len() / 2);
+  }
+);
+
+test_fn!(
+  fn rebase_set_converges() {
+    use protean::{impls::json, prelude::*};
+    use serde_json::{json, Value};
+
+    let base = json!({ "field": { "a": 1, "b": 2 } });
+    // Each edit either replaces the whole field or sets one key of it in a nested patch
+    let edits = vec![
+      (None, json!({ "a": 5 })),
+      (None, json!({ "c": 1 })),
+      (None, json!("other")),
+      (Some("a"), json!(3)),
+      (Some("a"), json!(7)),
+      (Some("b"), json!(4)),
+    ];
+    let patch = |(key, value): &(Option<&str>, Value)| {
+      let mut patch = Patch::new("Root".to_string());
+      match key {
+        None => patch
+          .add(
+            Action::Set,
+            OwnedValue::new("field".to_string(), value.clone()),
+            None,
+          )
+          .unwrap(),
+        Some(key) => {
+          let mut nested = Patch::new("field".to_string());
+          nested
+            .add(
+              Action::Set,
+              OwnedValue::new(key.to_string(), value.clone()),
+              None,
+            )
+            .unwrap();
+          patch
+            .add_action(PatchAction::from_patch("field".to_string(), nested))
+            .unwrap();
+        }
+      }
+      patch
+    };
+    let apply = |first: Patch, second: Patch| {
+      let mut target = base.clone();
+      json::apply_patch(&mut target, first).unwrap();
+      json::apply_patch(&mut target, second).unwrap();
+      target
+    };
+
+    // Whichever side goes first, both replacing the value or the same key keeps the same winner
+    for left in &edits {
+      for right in &edits {
+        let rebased_left = patch(left).rebase(&patch(right)).unwrap();
+        let rebased_right = patch(right).rebase(&patch(left)).unwrap();
+        assert_eq!(
+          apply(patch(left), rebased_right),
+          apply(patch(right), rebased_left),
+          "{:?} and {:?} did not converge",
+          left,
+          right
+        );
+      }
+    }
+  }
+);
+
+test_fn!(
+  fn rebase_nested() {
+    use protean::{impls::json, prelude::*};
+    use serde_json::json;
+
+    let base = json!({ "outer": { "list": ["a", "b", "c"] }, "other": 1 });
+    let nested = |act: ListAction| {
+      let mut inner = Patch::new("outer".to_string());
+      inner
+        .add(
+          Action::List(act),
+          OwnedValue::new("list".to_string(), json!("new")),
+          None,
+        )
+        .unwrap();
+      let mut patch = Patch::new("Root".to_string());
+      patch
+        .add_action(PatchAction::from_patch("outer".to_string(), inner))
+        .unwrap();
+      patch
+    };
+
+    // Removing "b" while someone else inserts at the front
+    let mut ours = nested(ListAction::Remove(1));
+    ours
+      .add(
+        Action::Set,
+        OwnedValue::new("other".to_string(), json!(2)),
+        None,
+      )
+      .unwrap();
+    let theirs = nested(ListAction::Insert(0));
+
+    let rebased = ours.rebase(&theirs).unwrap();
+    let mut target = base.clone();
+    json::apply_patch(&mut target, theirs).unwrap();
+    json::apply_patch(&mut target, rebased).unwrap();
+    assert_eq!(
+      target,
+      json!({ "outer": { "list": ["new", "a", "c"] }, "other": 2 })
+    );
+
+    // Removing an element that was already removed leaves nothing to do
+    let rebased = nested(ListAction::Remove(1))
+      .rebase(&nested(ListAction::Remove(1)))
+      .unwrap();
+    assert!(rebased.is_empty());
+  }
+);
+
+test_fn!(
+  fn rebase_steps() {
+    use protean::{impls::json, prelude::*};
+    use serde_json::{json, Value};
+
+    let base = json!({ "list": [10, 11, 12, 13, 14] });
+    let steps = |edits: Vec<(ListAction, Value)>| {
+      let mut patch = Patch::new("Root".to_string());
+      for (act, value) in edits {
+        patch
+          .add_step(PatchAction::new(
+            Action::List(act),
+            OwnedValue::new("list".to_string(), value),
+            None,
+          ))
+          .unwrap();
+      }
+      patch
+    };
+    let ours = || {
+      steps(vec![
+        (ListAction::Insert(0), json!("a")),
+        (ListAction::Remove(4), Value::Null),
+      ])
+    };
+    let theirs = || {
+      steps(vec![
+        (ListAction::Remove(1), Value::Null),
+        (ListAction::Append(), json!("b")),
+        (ListAction::Swap(0, 1), Value::Null),
+      ])
+    };
+
+    let mut left = base.clone();
+    json::apply_patch(&mut left, ours()).unwrap();
+    json::apply_patch(&mut left, theirs().rebase(&ours()).unwrap()).unwrap();
+    let mut right = base.clone();
+    json::apply_patch(&mut right, theirs()).unwrap();
+    json::apply_patch(&mut right, ours().rebase(&theirs()).unwrap()).unwrap();
+    assert_eq!(left, right);
+    assert_eq!(left, json!({ "list": ["a", 12, 10, 14, "b"] }));
+  }
+);
+
+test_fn!(
+  fn rebase_deleted_key() {
+    use protean::{impls::json, prelude::*};
+    use serde_json::json;
+
+    let base = json!({ "field": { "a": { "x": 1 }, "j": 1 } });
+    let edit = |name: &str| {
+      let mut patch = Patch::new(name.to_string());
+      patch
+        .add(
+          Action::Set,
+          OwnedValue::new("x".to_string(), json!(2)),
+          None,
+        )
+        .unwrap();
+      patch
+    };
+    let wrap = |action: PatchAction<'static>| {
+      let mut patch = Patch::new("Root".to_string());
+      patch.add_action(action).unwrap();
+      patch
+    };
+
+    // Editing a key through a nested patch or a map update, while the other side deletes it
+    let patches = || {
+      let mut nested = Patch::new("field".to_string());
+      nested
+        .add_action(PatchAction::from_patch("a".to_string(), edit("a")))
+        .unwrap();
+      vec![
+        wrap(PatchAction::from_patch("field".to_string(), nested)),
+        wrap(PatchAction::from_value(
+          Action::Map(MapAction::Update("a".to_string())),
+          PatchValue::Patch(edit("field")),
+          None,
+        )),
+        wrap(PatchAction::new(
+          Action::Map(MapAction::Delete("a".to_string())),
+          OwnedValue::new("field".to_string(), json!(null)),
+          None,
+        )),
+      ]
+    };
+    let apply = |first: Patch, second: Patch| {
+      let mut target = base.clone();
+      json::apply_patch(&mut target, first).unwrap();
+      json::apply_patch(&mut target, second).unwrap();
+      target
+    };
+
+    for (left, right) in [(0, 2), (1, 2)] {
+      let rebased_left = patches().remove(left).rebase(&patches()[right]).unwrap();
+      let rebased_right = patches().remove(right).rebase(&patches()[left]).unwrap();
+      let expected = json!({ "field": { "j": 1 } });
+      assert_eq!(apply(patches().remove(left), rebased_right), expected);
+      assert_eq!(apply(patches().remove(right), rebased_left), expected);
+    }
+  }
+);
@@ -0,0 +1,124 @@
+//! Patching types that only implement Serialize and Deserialize
+
+mod common;
+
+use common::test_fn;
+
+use protean::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Stands in for a type from another crate, which can't have Patchwork derived on it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Account {
+  user_name: String,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  email: Option<String>,
+  roles: Vec<String>,
+  limits: BTreeMap<String, u32>,
+  status: Status,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Status {
+  Active,
+  Suspended { reason: String },
+}
+
+fn account() -> Account {
+  Account {
+    user_name: "dave".to_string(),
+    email: Some("dave@example.com".to_string()),
+    roles: ["admin", "dev", "ops", "qa"]
+      .iter()
+      .map(|role| role.to_string())
+      .collect(),
+    limits: vec![("cpu".to_string(), 2), ("disk".to_string(), 10)]
+      .into_iter()
+      .collect(),
+    status: Status::Active,
+  }
+}
+
+test_fn!(
+  fn serde_diff_and_apply() {
+    let old = Serde(account());
+    let mut new = old.clone();
+    new.user_name = "david".to_string();
+    new.email = None;
+    new.roles.remove(1);
+    new.limits.insert("cpu".to_string(), 4);
+    new.status = Status::Suspended {
+      reason: "billing".to_string(),
+    };
+
+    // Fields use their serde names, and the skipped email is reset so serde fills in its default
+    let patch = old.diff(&new).unwrap();
+    let serialized = serde_json::to_value(&patch).unwrap();
+    let actions = &serialized["actions"];
+    assert_eq!(actions["userName"], json!(["Set", { "Value": "david" }]));
+    assert_eq!(actions["email"], json!(["Reset"]));
+    assert_eq!(actions["roles"], json!(["List.Remove", 1]));
+    assert_eq!(
+      actions["limits"][1]["Patch"]["actions"]["cpu"],
+      json!(["Set", { "Value": 4 }])
+    );
+    assert_eq!(
+      Serde::<Account>::get_name(),
+      std::any::type_name::<Account>()
+    );
+
+    let patch: Patch = serde_json::from_value(serialized).unwrap();
+    let mut target = old.clone();
+    let undo = target.apply(patch).unwrap();
+    assert_eq!(target, new);
+    target.apply(undo).unwrap();
+    assert_eq!(target, old);
+    assert!(old.diff(&old.clone()).unwrap().is_empty());
+  }
+);
+
+test_fn!(
+  fn serde_invalid_patch() {
+    let old = Serde(account());
+    let mut target = old.clone();
+
+    // A patch that leaves the value unreadable by serde is rejected as a whole
+    let patch: Patch = serde_json::from_value(json!({
+      "name": "Account",
+      "version": null,
+      "options": null,
+      "actions": {
+        "userName": ["Set", { "Value": "someone" }],
+        "roles": ["Set", { "Value": 12 }]
+      }
+    }))
+    .unwrap();
+    assert!(target.apply(patch).is_err());
+    assert_eq!(target, old);
+
+    // A required field can't be reset, as serde has no default to fill in
+    let patch: Patch = serde_json::from_value(json!({
+      "name": "Account",
+      "version": null,
+      "options": null,
+      "actions": { "userName": ["Reset"] }
+    }))
+    .unwrap();
+    assert!(target.apply(patch).is_err());
+    assert_eq!(target, old);
+
+    // A value that doesn't serialize as a map has no fields, which is an error rather than empty
+    let number = Serde(5);
+    assert!(matches!(
+      number.values(),
+      Err(ProteanError::InvalidPatchType)
+    ));
+    assert!(matches!(
+      number.as_patch(),
+      Err(ProteanError::InvalidPatchType)
+    ));
+  }
+);
@@ -0,0 +1,145 @@
+//! Adding and removing the members of unordered sets
+
+mod common;
+
+use common::test_fn;
+
+test_fn!(
+  fn diff_sets() {
+    use protean::{
+      impls::set::{apply_set, diff_set},
+      prelude::*,
+    };
+    use serde_json::json;
+    use std::collections::HashSet;
+
+    let set =
+      |values: &[&str]| -> HashSet<String> { values.iter().map(|v| v.to_string()).collect() };
+    let old = set(&["c", "a", "b"]);
+    let new = set(&["e", "b", "d", "c"]);
+
+    // The steps are sorted, so they are the same however the sets happen to iterate
+    let action = diff_set("tags", &old, &new).unwrap().unwrap();
+    let serialized = serde_json::to_value(&action).unwrap();
+    assert_eq!(
+      serialized,
+      json!([["Set.Remove", "a"], ["Set.Add", "d"], ["Set.Add", "e"]])
+    );
+    assert!(diff_set("tags", &old, &old.clone()).unwrap().is_none());
+
+    let patch: Patch = serde_json::from_value(json!({
+      "name": "Tags",
+      "version": null,
+      "options": null,
+      "actions": { "tags": serialized }
+    }))
+    .unwrap();
+    let mut target = old.clone();
+    let undo = apply_set(&mut target, patch.into_actions().next().unwrap()).unwrap();
+    assert_eq!(target, new);
+    apply_set(&mut target, undo).unwrap();
+    assert_eq!(target, old);
+  }
+);
+
+test_fn!(
+  fn set_actions() {
+    use protean::{impls::set::apply_set, prelude::*};
+    use serde_json::json;
+    use std::collections::BTreeSet;
+
+    let action = |act: SetAction, value: u32, allow_upsert: bool| {
+      let mut options = PatchOptions::default();
+      options.set_allow_upsert(allow_upsert);
+      let mut action = PatchAction::new(
+        Action::Members(act),
+        OwnedValue::new("ids".to_string(), json!(value)),
+        None,
+      );
+      action.inherit_options(&options);
+      action
+    };
+
+    let mut ids: BTreeSet<u32> = vec![1, 2].into_iter().collect();
+    apply_set(&mut ids, action(SetAction::Add, 2, true)).unwrap();
+    assert!(matches!(
+      apply_set(&mut ids, action(SetAction::Add, 2, false)),
+      Err(ProteanError::DuplicateKey)
+    ));
+    assert!(matches!(
+      apply_set(&mut ids, action(SetAction::Remove, 3, true)),
+      Err(ProteanError::KeyNotFound(_))
+    ));
+    apply_set(&mut ids, action(SetAction::Remove, 1, true)).unwrap();
+    assert_eq!(ids.into_iter().collect::<Vec<_>>(), vec![2]);
+  }
+);
+
+test_fn!(
+  fn rebase_sets() {
+    use protean::prelude::*;
+    use serde_json::json;
+
+    let patch = |act: SetAction, value: &str| {
+      let mut patch = Patch::new("Root".to_string());
+      patch
+        .add(
+          Action::Members(act),
+          OwnedValue::new("tags".to_string(), json!(value)),
+          None,
+        )
+        .unwrap();
+      patch
+    };
+
+    // Changes to different members pass each other untouched, and repeating one is dropped
+    let rebased = patch(SetAction::Add, "a")
+      .rebase(&patch(SetAction::Remove, "b"))
+      .unwrap();
+    assert_eq!(rebased, patch(SetAction::Add, "a"));
+    let rebased = patch(SetAction::Remove, "a")
+      .rebase(&patch(SetAction::Remove, "a"))
+      .unwrap();
+    assert!(rebased.is_empty());
+
+    // Adding a member the other side removed depends on which goes first
+    let rebased = patch(SetAction::Add, "a").rebase(&patch(SetAction::Remove, "a"));
+    assert!(matches!(rebased, Err(ProteanError::RebaseConflict(path)) if path == "tags"));
+  }
+);
+
+test_fn!(
+  fn compose_sets() {
+    use protean::{impls::json, prelude::*};
+    use serde_json::json;
+
+    let patch = |act: SetAction| {
+      let mut patch = Patch::new("Root".to_string());
+      patch
+        .add(
+          Action::Members(act),
+          OwnedValue::new("tags".to_string(), json!(1)),
+          None,
+        )
+        .unwrap();
+      patch
+    };
+
+    // Adding a member that may already be there and then removing it still removes it
+    for start in [json!({ "tags": [1] }), json!({ "tags": [] })] {
+      let mut sequential = start.clone();
+      json::apply_patch(&mut sequential, patch(SetAction::Add)).unwrap();
+      json::apply_patch(&mut sequential, patch(SetAction::Remove)).unwrap();
+
+      let composed = Patch::compose(patch(SetAction::Add), patch(SetAction::Remove)).unwrap();
+      let mut target = start.clone();
+      json::apply_patch(&mut target, composed).unwrap();
+      assert_eq!(target, sequential);
+      assert_eq!(target, json!({ "tags": [] }));
+    }
+
+    // A member can only be removed if it was there, so adding it back is a no-op
+    let composed = Patch::compose(patch(SetAction::Remove), patch(SetAction::Add)).unwrap();
+    assert!(composed.is_empty());
+  }
+);
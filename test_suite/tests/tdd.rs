@@ -12,7 +12,7 @@ test_fn!(
     let db = Db::new();
 
     // Full export for an empty db
-    let patch: Patch = db.as_patch();
+    let patch: Patch = db.as_patch().unwrap();
 
     println!("{:#?}", patch);
 
@@ -0,0 +1,239 @@
+//! Options, Results, smart pointers, tuples and arrays
+
+mod common;
+
+use common::test_fn;
+
+use protean::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+
+/// Diff old against new, check it serializes as expected, then apply it and its undo to a copy
+fn round_trip<T>(old: T, new: T, expected: serde_json::Value)
+where
+  T: for<'a> Patchable<'a> + Clone + PartialEq + Serialize + DeserializeOwned,
+{
+  let action = old.diff_value("field", &new).unwrap();
+  let serialized = serde_json::to_value(&action).unwrap();
+  assert_eq!(serialized, expected);
+
+  let action = match action {
+    Some(action) => action.try_clone().unwrap(),
+    None => return assert!(old == new),
+  };
+
+  // The same patch works on the JSON form of the value
+  let mut value = serde_json::to_value(&old).unwrap();
+  protean::impls::json::apply_action(&mut value, action.try_clone().unwrap()).unwrap();
+  assert_eq!(value, serde_json::to_value(&new).unwrap());
+
+  let mut target = old.clone();
+  let undo = target.apply_value(action).unwrap();
+  assert!(target == new);
+  target.apply_value(undo).unwrap();
+  assert!(target == old);
+}
+
+/// The serialized form of a nested patch for the field, as made by tuples and Results
+fn nested(actions: serde_json::Value) -> serde_json::Value {
+  json!(["Set", { "Patch": {
+    "name": "field",
+    "version": null,
+    "options": null,
+    "actions": actions
+  }}])
+}
+
+test_fn!(
+  fn diff_options() {
+    round_trip(None, Some(3u8), json!(["Set", { "Value": 3 }]));
+    round_trip(Some(3u8), None, json!(["Set", { "Value": null }]));
+    round_trip(Some(3u8), Some(4), json!(["Set", { "Value": 4 }]));
+    round_trip::<Option<u8>>(None, None, json!(null));
+
+    // Some to Some is diffed by the inner value, so a nested Option only changes what is inside
+    round_trip(
+      Some((1u8, "a".to_string())),
+      Some((1, "b".to_string())),
+      nested(json!({
+        "1": ["Set", { "Value": "b" }]
+      })),
+    );
+
+    let mut value: Option<(u8, u8)> = None;
+    let action = Some((1u8, 1u8))
+      .diff_value("field", &Some((1, 2)))
+      .unwrap()
+      .unwrap();
+    assert!(value.apply_value(action).is_err());
+    assert_eq!(value, None);
+
+    // Clearing None leaves it as it is, the same as a JSON null
+    let clear = || {
+      PatchAction::new(
+        Action::Clear,
+        OwnedValue::new("field".to_string(), json!(null)),
+        None,
+      )
+    };
+    let mut json = json!(null);
+    protean::impls::json::apply_action(&mut json, clear()).unwrap();
+    let undo = value.apply_value(clear()).unwrap();
+    assert_eq!(value, None);
+    value.apply_value(undo).unwrap();
+    assert_eq!(value, None);
+  }
+);
+
+test_fn!(
+  fn diff_results() {
+    let ok = |value: &str| -> Result<String, u32> { Ok(value.to_string()) };
+    round_trip(
+      ok("a"),
+      Err(404),
+      json!(["Set", { "Value": { "Err": 404 } }]),
+    );
+    round_trip(
+      ok("a"),
+      ok("b"),
+      nested(json!({
+        "Ok": ["Set", { "Value": "b" }]
+      })),
+    );
+
+    // A patch for the other variant can't be applied
+    let mut value: Result<String, u32> = Err(500);
+    let (old, new) = (ok("a"), ok("b"));
+    let action = old.diff_value("field", &new).unwrap().unwrap();
+    assert!(value.apply_value(action).is_err());
+    assert_eq!(value, Err(500));
+  }
+);
+
+test_fn!(
+  fn diff_pointers() {
+    use std::{borrow::Cow, rc::Rc, sync::Arc};
+
+    round_trip(
+      Box::new(1i64),
+      Box::new(-1),
+      json!(["Set", { "Value": -1 }]),
+    );
+    round_trip(
+      Arc::new(true),
+      Arc::new(false),
+      json!(["Set", { "Value": false }]),
+    );
+
+    // The other owners of a shared value keep the original
+    let shared = Rc::new("old".to_string());
+    let mut value = shared.clone();
+    let action = shared
+      .diff_value("field", &Rc::new("new".to_string()))
+      .unwrap()
+      .unwrap()
+      .try_clone()
+      .unwrap();
+    value.apply_value(action).unwrap();
+    assert_eq!((shared.as_str(), value.as_str()), ("old", "new"));
+
+    let old: Cow<str> = Cow::Borrowed("old");
+    let new: Cow<str> = Cow::Owned("new".to_string());
+    let mut value = old.clone();
+    value
+      .apply_value(old.diff_value("field", &new).unwrap().unwrap())
+      .unwrap();
+    assert_eq!(value, "new");
+  }
+);
+
+test_fn!(
+  fn diff_tuples_and_arrays() {
+    round_trip(
+      (1u8, 'a', false),
+      (2u8, 'a', true),
+      nested(json!({
+        "0": ["Set", { "Value": 2 }],
+        "2": ["Set", { "Value": true }],
+      })),
+    );
+    round_trip(
+      [1.5f32, 2.5, 3.5],
+      [1.5, 2.5, 4.5],
+      nested(json!({
+        "2": ["Set", { "Value": 4.5 }]
+      })),
+    );
+    round_trip(
+      [Some(1u16), None],
+      [None, Some(2)],
+      nested(json!({
+        "0": ["Set", { "Value": null }],
+        "1": ["Set", { "Value": 2 }],
+      })),
+    );
+
+    // Elements that aren't there are rejected, and the earlier elements are rolled back
+    let patch: Patch = serde_json::from_value(json!({
+      "name": "Pair",
+      "version": null,
+      "options": null,
+      "actions": { "pair": ["Set", { "Patch": { "name": "pair", "version": null, "options": null,
+        "actions": { "0": ["Set", { "Value": 9 }], "2": ["Set", { "Value": 9 }] }
+      }}]}
+    }))
+    .unwrap();
+    let mut value = (1u8, 2u8);
+    let result = value.apply_value(patch.into_actions().next().unwrap());
+    assert!(matches!(result, Err(ProteanError::IndexOutOfRange(2))));
+    assert_eq!(value, (1, 2));
+  }
+);
+
+test_fn!(
+  fn expected_parts() {
+    use protean::hash::content_hash;
+
+    let patch = |on_mismatch: Option<OnMismatch>| {
+      let mut patch = Patch::new("field".to_string());
+      patch
+        .add(
+          Action::Set,
+          OwnedValue::new("0".to_string(), json!(5)),
+          Some(content_hash(&json!(1))),
+        )
+        .unwrap();
+      patch
+        .add(
+          Action::Set,
+          OwnedValue::new("1".to_string(), json!("b")),
+          None,
+        )
+        .unwrap();
+      if let Some(on_mismatch) = on_mismatch {
+        let mut options = PatchOptions::default();
+        options.set_on_mismatch(on_mismatch);
+        patch.set_options(options);
+      }
+      PatchAction::from_patch("field".to_string(), patch)
+    };
+
+    // The part no longer matches, which is reported by its own name and rolls back the other part
+    let mut value = (2u8, "a".to_string());
+    match value.apply_value(patch(None)) {
+      Err(ProteanError::ExpectedMismatch { path, .. }) => assert_eq!(path, "0"),
+      result => panic!("Expected a mismatch, got {:?}", result),
+    }
+    assert_eq!(value, (2, "a".to_string()));
+
+    // Options set on the patch, or inherited from a parent, decide what happens instead
+    value.apply_value(patch(Some(OnMismatch::Skip))).unwrap();
+    assert_eq!(value, (2, "b".to_string()));
+    let mut action = patch(None);
+    let mut options = PatchOptions::default();
+    options.set_on_mismatch(OnMismatch::Force);
+    action.inherit_options(&options);
+    value.apply_value(action).unwrap();
+    assert_eq!(value, (5, "b".to_string()));
+  }
+);